@@ -2,48 +2,413 @@
 
 To run:
 
-cargo run --  --message "Off to the bunker. Every person for themselves" --encrypt --shift 10
+cargo run -- encrypt --message "Off to the bunker. Every person for themselves" --shift 10
 
 To decrypt:
 
-cargo run --  --message "Ypp dy dro lexuob. Ofobi zobcyx pyb drowcovfoc" --decrypt --shift 10
+cargo run -- decrypt --message "Ypp dy dro lexuob. Ofobi zobcyx pyb drowcovfoc" --shift 10
 
-*/
+The shift must be between 1 and 25. Without --message the text is read from
+--input, or stdin, and written to --output, or stdout, in chunks, so files
+of any size can be processed:
+
+cat secrets.txt | cargo run -- encrypt --shift 10 > secrets.enc
+cargo run -- decrypt --shift 10 --input secrets.enc --output secrets.txt
+
+A quoted glob processes many files at once into an output directory, keeping
+their file names:
+
+cargo run -- encrypt --glob "exports/report-*.txt" --output-dir encrypted
+
+--in-place rewrites the input files instead, after copying each one to a
+backup with the --backup-suffix (".bak" by default). Files that already end
+with the suffix are backups of an earlier run and are left out of the glob:
+
+cargo run -- decrypt --glob "encrypted/report-*.txt" --in-place
 
+Without the shift, crack picks the one whose letter frequencies look most
+like English, and bruteforce lists all 25 candidates:
 
-use caeser_cipher_cli::{decrypt, encrypt};
-use clap::Parser;
+cargo run -- crack --message "Ypp dy dro lexuob. Ofobi zobcyx pyb drowcovfoc"
+cargo run -- bruteforce --input secrets.enc
+
+Errors exit with status 1, invalid arguments with status 2.
+
+*/
+
+use caesar_cipher::{crack, decrypt, decrypt_stream, encrypt, encrypt_stream};
+use clap::{Args, Parser, Subcommand};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
 /// CLI tool to encrypt and decrypt messages using the caeser cipher
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
     /// Encrypt the message
-    #[arg(short, long)]
-    encrypt: bool,
+    Encrypt(CipherArgs),
+    /// Decrypt the message
+    Decrypt(CipherArgs),
+    /// Find the shift with English letter frequencies and decrypt the message
+    Crack(Source),
+    /// Print the message decrypted with every shift from 1 to 25
+    Bruteforce(Source),
+}
 
-    /// decrypt the message
-    #[arg(short, long)]
-    decrypt: bool,
+#[derive(Args, Debug)]
+struct Source {
+    /// The message to process, read from --input or stdin when omitted
+    #[arg(short, long, conflicts_with = "input")]
+    message: Option<String>,
 
-    /// The message to encrypt or decrypt
+    /// File to read instead of stdin
     #[arg(short, long)]
-    message: String,
+    input: Option<PathBuf>,
+}
 
+impl Source {
+    fn read(&self) -> io::Result<String> {
+        match (&self.message, &self.input) {
+            (Some(message), _) => Ok(message.clone()),
+            (None, Some(input)) => fs::read_to_string(input),
+            (None, None) => io::read_to_string(io::stdin()),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct CipherArgs {
     /// The shift to use for the cipher
     /// Must be between 1 and 25, the default is 3
-    #[arg(short, long, default_value = "3")]
+    #[arg(short, long, default_value = "3", value_parser = clap::value_parser!(u8).range(1..=25))]
     shift: u8,
+
+    #[command(flatten)]
+    source: Source,
+
+    /// File to write instead of stdout
+    #[arg(short, long, conflicts_with_all = ["output_dir", "in_place"])]
+    output: Option<PathBuf>,
+
+    /// Glob of files to process in batch, quoted so the shell does not expand it
+    #[arg(short, long, conflicts_with_all = ["message", "input"])]
+    glob: Option<String>,
+
+    /// Directory for the files of a batch, created when missing
+    #[arg(long, requires = "glob", conflicts_with = "in_place")]
+    output_dir: Option<PathBuf>,
+
+    /// Overwrite the input files, after copying each one to a backup.
+    /// A glob skips the files that already end with the backup suffix
+    #[arg(long, conflicts_with = "message")]
+    in_place: bool,
+
+    /// Suffix added to the file name of the backups of --in-place
+    #[arg(long, default_value = ".bak")]
+    backup_suffix: String,
+}
+
+// The shift of the encrypt or decrypt command, in its direction
+#[derive(Debug, Clone, Copy)]
+struct Rotation {
+    shift: u8,
+    decrypt: bool,
+}
+
+impl Rotation {
+    fn text(&self, text: &str) -> String {
+        if self.decrypt {
+            decrypt(text, self.shift)
+        } else {
+            encrypt(text, self.shift)
+        }
+    }
+
+    // Rotate reader into writer in chunks, without holding it all in memory
+    fn stream<R: Read, W: Write>(&self, reader: R, writer: W) -> io::Result<()> {
+        if self.decrypt {
+            decrypt_stream(reader, writer, self.shift)
+        } else {
+            encrypt_stream(reader, writer, self.shift)
+        }
+    }
+}
+
+// Whether two paths name the same existing file
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn transform_file(input: &Path, output: &Path, rotation: Rotation) -> Result<(), Box<dyn Error>> {
+    // Creating the output would truncate the input before it is read
+    if same_file(input, output) {
+        return Err(format!(
+            "{} is both the input and the output, use --in-place to overwrite it",
+            input.display()
+        )
+        .into());
+    }
+    let reader = File::open(input)?;
+    let writer = BufWriter::new(File::create(output)?);
+    rotation
+        .stream(reader, writer)
+        .map_err(|err| format!("{}: {}", input.display(), err))?;
+    Ok(())
+}
+
+// Add a suffix to the file name of a path
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+// Back the file up, write the result next to it and rename it over the original
+fn transform_in_place(
+    path: &Path,
+    backup_suffix: &str,
+    rotation: Rotation,
+) -> Result<(), Box<dyn Error>> {
+    fs::copy(path, with_suffix(path, backup_suffix))?;
+    let tmp = with_suffix(path, ".tmp");
+    if let Err(err) = transform_file(path, &tmp, rotation) {
+        // Best effort, the error of the transform is the one worth reporting
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+// Process every file matching the glob, returns the number of files
+fn transform_batch(
+    args: &CipherArgs,
+    pattern: &str,
+    rotation: Rotation,
+) -> Result<usize, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    if args.in_place {
+        // Backups of earlier runs would be rewritten and backed up again
+        let suffix = args.backup_suffix.as_str();
+        paths.retain(|path| {
+            suffix.is_empty()
+                || !path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().ends_with(suffix))
+        });
+        for path in &paths {
+            transform_in_place(path, &args.backup_suffix, rotation)?;
+        }
+        return Ok(paths.len());
+    }
+
+    let output_dir = args
+        .output_dir
+        .as_ref()
+        .ok_or("--glob needs either --output-dir or --in-place")?;
+    // Files from different directories could land on the same output name
+    let mut names = HashSet::new();
+    for path in &paths {
+        if !names.insert(path.file_name()) {
+            return Err(format!(
+                "more than one file is named {:?}",
+                path.file_name().unwrap()
+            )
+            .into());
+        }
+        // Checked before any file is written, so a batch fails as a whole
+        if same_file(path, &output_dir.join(path.file_name().unwrap())) {
+            return Err(format!(
+                "{} would be overwritten by its own output, use --in-place instead",
+                path.display()
+            )
+            .into());
+        }
+    }
+    fs::create_dir_all(output_dir)?;
+    for path in &paths {
+        transform_file(path, &output_dir.join(path.file_name().unwrap()), rotation)?;
+    }
+    Ok(paths.len())
+}
+
+fn run_cipher(args: &CipherArgs, rotation: Rotation) -> Result<(), Box<dyn Error>> {
+    if let Some(message) = &args.source.message {
+        match &args.output {
+            Some(output) => fs::write(output, rotation.text(message) + "\n")?,
+            None => println!("{}", rotation.text(message)),
+        }
+        return Ok(());
+    }
+
+    if let Some(pattern) = &args.glob {
+        let count = transform_batch(args, pattern, rotation)?;
+        eprintln!("Processed {} files", count);
+        return Ok(());
+    }
+
+    match (&args.source.input, &args.output) {
+        (Some(input), _) if args.in_place => {
+            transform_in_place(input, &args.backup_suffix, rotation)
+        }
+        (None, _) if args.in_place => Err("--in-place needs --input or --glob".into()),
+        (Some(input), Some(output)) => transform_file(input, output, rotation),
+        (Some(input), None) => Ok(rotation.stream(File::open(input)?, io::stdout().lock())?),
+        (None, Some(output)) => {
+            let writer = BufWriter::new(File::create(output)?);
+            Ok(rotation.stream(io::stdin().lock(), writer)?)
+        }
+        (None, None) => Ok(rotation.stream(io::stdin().lock(), io::stdout().lock())?),
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Commands::Encrypt(args) => {
+            let rotation = Rotation {
+                shift: args.shift,
+                decrypt: false,
+            };
+            run_cipher(&args, rotation)
+        }
+        Commands::Decrypt(args) => {
+            let rotation = Rotation {
+                shift: args.shift,
+                decrypt: true,
+            };
+            run_cipher(&args, rotation)
+        }
+        Commands::Crack(source) => {
+            let text = source.read()?;
+            let (shift, plain) = crack(&text).ok_or("the message has no letters to analyse")?;
+            // stdout carries the plaintext, so the shift goes to stderr
+            eprintln!("Shift: {}", shift);
+            println!("{}", plain.trim_end_matches('\n'));
+            Ok(())
+        }
+        Commands::Bruteforce(source) => {
+            let text = source.read()?;
+            for shift in 1..26 {
+                println!("{:>2}: {}", shift, decrypt(text.trim_end(), shift));
+            }
+            Ok(())
+        }
+    }
 }
 
 // run it
+// Exits with 1 on errors, clap exits with 2 on invalid arguments
 fn main() {
-    let args = Args::parse();
-    if args.encrypt {
-        println!("{}", encrypt(&args.message, args.shift));
-    } else if args.decrypt {
-        println!("{}", decrypt(&args.message, args.shift));
-    } else {
-        println!("Please specify either --encrypt or --decrypt");
+    let cli = Cli::parse();
+    if let Err(err) = run(cli) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher_args(args: &[&str]) -> CipherArgs {
+        let cli = Cli::parse_from(["caesar", "encrypt"].iter().chain(args));
+        match cli.command {
+            Commands::Encrypt(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    const ENCRYPT: Rotation = Rotation {
+        shift: 3,
+        decrypt: false,
+    };
+
+    #[test]
+    fn test_transform_file_refuses_its_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.txt");
+        fs::write(&path, "attack at dawn").unwrap();
+
+        let err = transform_file(&path, &path, ENCRYPT).unwrap_err();
+        assert!(err.to_string().contains("both the input and the output"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "attack at dawn");
+    }
+
+    #[test]
+    fn test_transform_batch_rejects_name_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["a", "b"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+            fs::write(dir.path().join(sub).join("x.txt"), "abc").unwrap();
+        }
+        let pattern = dir.path().join("*").join("x.txt");
+        let output_dir = dir.path().join("out");
+        let args = cipher_args(&[
+            "--glob",
+            pattern.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+        ]);
+
+        let err = transform_batch(&args, args.glob.as_ref().unwrap(), ENCRYPT).unwrap_err();
+        assert!(err.to_string().contains("more than one file"));
+        assert!(!output_dir.exists());
+    }
+
+    #[test]
+    fn test_in_place_round_trip_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.txt");
+        fs::write(&path, "Attack at dawn").unwrap();
+        let pattern = dir.path().join("*");
+        let args = cipher_args(&["--glob", pattern.to_str().unwrap(), "--in-place"]);
+        let pattern = args.glob.as_ref().unwrap();
+
+        assert_eq!(transform_batch(&args, pattern, ENCRYPT).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Dwwdfn dw gdzq");
+
+        // The backup of the first run is not part of the second one
+        let decrypt = Rotation {
+            decrypt: true,
+            ..ENCRYPT
+        };
+        assert_eq!(transform_batch(&args, pattern, decrypt).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Attack at dawn");
+        assert_eq!(
+            fs::read_to_string(with_suffix(&path, ".bak")).unwrap(),
+            "Dwwdfn dw gdzq"
+        );
+        assert!(!with_suffix(&path, ".bak.bak").exists());
+    }
+
+    #[test]
+    fn test_transform_in_place_removes_temp_file_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("binary.dat");
+        fs::write(&path, [0xff, 0xfe, 0x00]).unwrap();
+
+        assert!(transform_in_place(&path, ".bak", ENCRYPT).is_err());
+        assert!(!with_suffix(&path, ".tmp").exists());
+        assert_eq!(fs::read(&path).unwrap(), [0xff, 0xfe, 0x00]);
     }
 }
@@ -0,0 +1,166 @@
+/*
+The set of letters a shift cipher rotates through.
+
+Letters are given in lowercase and in order. When every letter has a single,
+distinct uppercase form, uppercase letters rotate through the uppercase
+alphabet in step, so case is preserved. Otherwise (digits, symbols, ß) only
+the characters exactly as listed are rotated. Anything outside the alphabet
+passes through unchanged.
+
+Built-in alphabets:
+
+latin        a-z, the default
+latin1       a-z plus the Latin-1 letters à-þ (ß and ÿ have no Latin-1 uppercase)
+portuguese   a-z plus á â ã à ç é ê í ó ô õ ú
+
+Shifts of any size are reduced modulo the number of letters.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alphabet {
+    lower: Vec<char>,
+    // Empty when the letters have no uppercase forms
+    upper: Vec<char>,
+    // Position of each letter and whether it is uppercase
+    index: HashMap<char, (usize, bool)>,
+}
+
+impl Alphabet {
+    // An alphabet from its letters in order, which must be distinct
+    pub fn new(letters: &str) -> Result<Alphabet, String> {
+        let lower: Vec<char> = letters.chars().collect();
+        if lower.is_empty() {
+            return Err("alphabet has no letters".to_string());
+        }
+
+        let mut index = HashMap::new();
+        for (i, &c) in lower.iter().enumerate() {
+            if index.insert(c, (i, false)).is_some() {
+                return Err(format!("letter '{}' appears twice in the alphabet", c));
+            }
+        }
+
+        let upper: Vec<char> = lower
+            .iter()
+            .filter_map(|&c| {
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(u), None) if u != c && !index.contains_key(&u) => Some(u),
+                    _ => None,
+                }
+            })
+            .collect();
+        let mut alphabet = Alphabet {
+            lower,
+            upper: Vec::new(),
+            index,
+        };
+        if upper.len() == alphabet.lower.len() {
+            for (i, &c) in upper.iter().enumerate() {
+                alphabet.index.insert(c, (i, true));
+            }
+            alphabet.upper = upper;
+        }
+        Ok(alphabet)
+    }
+
+    pub fn latin() -> Alphabet {
+        Alphabet::new("abcdefghijklmnopqrstuvwxyz").unwrap()
+    }
+
+    pub fn latin1() -> Alphabet {
+        let accented = ('\u{e0}'..='\u{fe}').filter(|&c| c != '\u{f7}');
+        let letters: String = ('a'..='z').chain(accented).collect();
+        Alphabet::new(&letters).unwrap()
+    }
+
+    pub fn portuguese() -> Alphabet {
+        Alphabet::new("abcdefghijklmnopqrstuvwxyzáâãàçéêíóôõú").unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lower.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lower.is_empty()
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.index.contains_key(&c)
+    }
+
+    // Move a letter shift places forward, other characters are returned as is
+    pub fn rotate(&self, c: char, shift: usize) -> char {
+        match self.index.get(&c) {
+            Some(&(i, upper)) => {
+                let letters = if upper { &self.upper } else { &self.lower };
+                letters[(i + shift % self.len()) % self.len()]
+            }
+            None => c,
+        }
+    }
+
+    // The shift that undoes a rotation by shift
+    pub fn inverse(&self, shift: usize) -> usize {
+        (self.len() - shift % self.len()) % self.len()
+    }
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Alphabet::latin()
+    }
+}
+
+impl fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lower.iter().try_for_each(|c| write!(f, "{}", c))
+    }
+}
+
+// A built-in alphabet by name, or the letters of a custom one
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latin" => Ok(Alphabet::latin()),
+            "latin1" => Ok(Alphabet::latin1()),
+            "portuguese" => Ok(Alphabet::portuguese()),
+            _ => Alphabet::new(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_keeps_case() {
+        let latin = Alphabet::latin();
+        assert_eq!(latin.rotate('z', 1), 'a');
+        assert_eq!(latin.rotate('Z', 27), 'A');
+        assert_eq!(latin.rotate('é', 3), 'é');
+
+        let portuguese = Alphabet::portuguese();
+        assert_eq!(portuguese.len(), 38);
+        assert_eq!(portuguese.rotate('z', 1), 'á');
+        assert_eq!(portuguese.rotate('Ú', 1), 'A');
+        assert_eq!(Alphabet::latin1().len(), 56);
+    }
+
+    #[test]
+    fn test_custom_alphabet() {
+        let digits: Alphabet = "0123456789".parse().unwrap();
+        assert_eq!(digits.rotate('9', 2), '1');
+        assert_eq!(digits.rotate('a', 2), 'a');
+        assert!(Alphabet::new("abca").is_err());
+        assert!(Alphabet::new("").is_err());
+    }
+}
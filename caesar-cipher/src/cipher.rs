@@ -0,0 +1,218 @@
+/*
+A Cipher trait so every classical cipher is used through the same interface.
+
+Implementations:
+
+Caesar     shift every letter of an Alphabet by a fixed amount
+Rot13      Caesar with a shift of 13, its own inverse
+Atbash     mirror the alphabet, a <-> z, b <-> y, ...
+Affine     E(x) = (a * x + b) mod 26, a must be coprime with 26
+Vigenere   shift each letter by the next letter of a keyword
+
+Case is preserved. Caesar rotates through any Alphabet, the others work on
+a-z; characters outside the alphabet pass through unchanged. Vigenere only
+advances through the keyword on letters.
+*/
+
+use crate::alphabet::Alphabet;
+
+pub trait Cipher {
+    fn encrypt(&self, text: &str) -> String;
+    fn decrypt(&self, text: &str) -> String;
+}
+
+// Apply f to the alphabet index (0..26) of every ASCII letter, keeping its case
+fn map_letters<F: FnMut(u8) -> u8>(text: &str, mut f: F) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphabetic() {
+                let base = if c.is_ascii_lowercase() { b'a' } else { b'A' };
+                (base + f(c as u8 - base) % 26) as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+pub struct Caesar {
+    shift: usize,
+    alphabet: Alphabet,
+}
+
+impl Caesar {
+    // A shift over a-z
+    pub fn new(shift: usize) -> Caesar {
+        Caesar::with_alphabet(shift, Alphabet::latin())
+    }
+
+    // Any shift is accepted and reduced modulo the size of the alphabet
+    pub fn with_alphabet(shift: usize, alphabet: Alphabet) -> Caesar {
+        Caesar {
+            shift: shift % alphabet.len(),
+            alphabet,
+        }
+    }
+
+    pub fn shift(&self) -> usize {
+        self.shift
+    }
+
+    fn rotate(&self, text: &str, shift: usize) -> String {
+        text.chars()
+            .map(|c| self.alphabet.rotate(c, shift))
+            .collect()
+    }
+}
+
+impl Cipher for Caesar {
+    fn encrypt(&self, text: &str) -> String {
+        self.rotate(text, self.shift)
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        self.rotate(text, self.alphabet.inverse(self.shift))
+    }
+}
+
+pub struct Rot13;
+
+impl Cipher for Rot13 {
+    fn encrypt(&self, text: &str) -> String {
+        Caesar::new(13).encrypt(text)
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        self.encrypt(text)
+    }
+}
+
+pub struct Atbash;
+
+impl Cipher for Atbash {
+    fn encrypt(&self, text: &str) -> String {
+        map_letters(text, |x| 25 - x)
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        self.encrypt(text)
+    }
+}
+
+pub struct Affine {
+    a: u8,
+    b: u8,
+    // Multiplicative inverse of a modulo 26
+    a_inv: u8,
+}
+
+impl Affine {
+    // Fails when a has no inverse modulo 26, the ciphertext could not be decrypted
+    pub fn new(a: u8, b: u8) -> Result<Affine, String> {
+        let a = a % 26;
+        let a_inv = (1..26)
+            .find(|&i| (a as u32 * i as u32) % 26 == 1)
+            .ok_or_else(|| format!("a = {} is not coprime with 26", a))?;
+        Ok(Affine {
+            a,
+            b: b % 26,
+            a_inv,
+        })
+    }
+}
+
+impl Cipher for Affine {
+    fn encrypt(&self, text: &str) -> String {
+        map_letters(text, |x| {
+            ((self.a as u32 * x as u32 + self.b as u32) % 26) as u8
+        })
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        map_letters(text, |y| {
+            ((self.a_inv as u32 * (y as u32 + 26 - self.b as u32)) % 26) as u8
+        })
+    }
+}
+
+pub struct Vigenere {
+    // Shift of every keyword letter, a = 0
+    shifts: Vec<u8>,
+}
+
+impl Vigenere {
+    // The keyword must contain at least one letter, anything else is ignored
+    pub fn new(keyword: &str) -> Result<Vigenere, String> {
+        let shifts: Vec<u8> = keyword
+            .chars()
+            .filter(|c| c.is_ascii_alphabetic())
+            .map(|c| c.to_ascii_lowercase() as u8 - b'a')
+            .collect();
+        if shifts.is_empty() {
+            return Err(format!("keyword '{}' has no letters", keyword));
+        }
+        Ok(Vigenere { shifts })
+    }
+}
+
+impl Cipher for Vigenere {
+    fn encrypt(&self, text: &str) -> String {
+        let mut key = self.shifts.iter().cycle();
+        map_letters(text, |x| x + key.next().unwrap())
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        let mut key = self.shifts.iter().cycle();
+        map_letters(text, |x| x + 26 - key.next().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_caesar_round_trips_every_shift(text in "\\PC*", shift in any::<usize>()) {
+            for alphabet in [Alphabet::latin(), Alphabet::latin1(), Alphabet::portuguese()] {
+                let caesar = Caesar::with_alphabet(shift, alphabet);
+                prop_assert_eq!(caesar.decrypt(&caesar.encrypt(&text)), text.clone());
+            }
+        }
+
+        #[test]
+        fn test_caesar_shift_is_modulo_alphabet(text in "[a-zA-Z ]*", shift in 0usize..26) {
+            let caesar = Caesar::new(shift);
+            prop_assert_eq!(caesar.encrypt(&text), Caesar::new(shift + 26 * 7).encrypt(&text));
+        }
+    }
+
+    #[test]
+    fn test_known_ciphertexts() {
+        assert_eq!(Rot13.encrypt("Hello, World!"), "Uryyb, Jbeyq!");
+        assert_eq!(Atbash.encrypt("Hello"), "Svool");
+        assert_eq!(Affine::new(5, 8).unwrap().encrypt("affine"), "ihhwvc");
+        assert_eq!(
+            Vigenere::new("lemon").unwrap().encrypt("attack at dawn"),
+            "lxfopv ef rnhr"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "The quick brown fox jumps over the lazy dog!";
+        let ciphers: Vec<Box<dyn Cipher>> = vec![
+            Box::new(Caesar::new(3)),
+            Box::new(Rot13),
+            Box::new(Atbash),
+            Box::new(Affine::new(7, 3).unwrap()),
+            Box::new(Vigenere::new("Lemon").unwrap()),
+        ];
+        for cipher in ciphers {
+            assert_eq!(cipher.decrypt(&cipher.encrypt(text)), text);
+        }
+        assert!(Affine::new(13, 1).is_err());
+        assert!(Vigenere::new("42").is_err());
+    }
+}
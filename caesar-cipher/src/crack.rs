@@ -0,0 +1,73 @@
+/*
+Find the shift of a Caesar ciphertext without the key.
+
+crack tries every shift from 1 to 25 and keeps the one whose letter
+frequencies are closest to English, measured with the chi-squared
+statistic. Shift 0 would leave the text as it is, so it is never reported.
+Only the letters a-z are counted, spaces and punctuation are ignored.
+*/
+
+use crate::cipher::{Caesar, Cipher};
+
+// Expected frequencies below this (in percent) would dominate chi-squared
+const MIN_FREQUENCY: f64 = 0.01;
+
+// Frequencies of a-z in English text, in percent
+pub const ENGLISH_FREQUENCIES: [f64; 26] = [
+    8.2, 1.5, 2.8, 4.3, 12.7, 2.2, 2.0, 6.1, 7.0, 0.15, 0.77, 4.0, 2.4, 6.7, 7.5, 1.9, 0.095, 6.0,
+    6.3, 9.1, 2.8, 0.98, 2.4, 0.15, 2.0, 0.074,
+];
+
+// Occurrences of each letter a-z, ignoring case
+pub fn letter_counts(text: &str) -> [u32; 26] {
+    let mut counts = [0; 26];
+    for c in text.chars().filter(char::is_ascii_alphabetic) {
+        counts[(c.to_ascii_lowercase() as u8 - b'a') as usize] += 1;
+    }
+    counts
+}
+
+// Chi-squared distance between the letters of text and the expected percent of each
+// letter a-z, such as ENGLISH_FREQUENCIES, None without letters
+pub fn chi_squared(text: &str, expected: &[f64; 26]) -> Option<f64> {
+    let counts = letter_counts(text);
+    let total: u32 = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let score = counts
+        .iter()
+        .zip(expected)
+        .map(|(&count, freq)| {
+            let expected = total as f64 * freq.max(MIN_FREQUENCY) / 100.0;
+            (count as f64 - expected).powi(2) / expected
+        })
+        .sum();
+    Some(score)
+}
+
+// The most likely shift and the decrypted text, None when text has no letters
+pub fn crack(text: &str) -> Option<(u8, String)> {
+    (1..26)
+        .map(|shift| (shift, Caesar::new(shift as usize).decrypt(text)))
+        .filter_map(|(shift, plain)| {
+            chi_squared(&plain, &ENGLISH_FREQUENCIES).map(|score| (score, shift, plain))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, shift, plain)| (shift, plain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crack() {
+        let plain = "Off to the bunker. Every person for themselves";
+        let cipher = Caesar::new(10).encrypt(plain);
+        assert_eq!(crack(&cipher), Some((10, plain.to_string())));
+        assert_eq!(crack("1234 !"), None);
+        // Plaintext is already the best fit, but shift 0 is not a candidate
+        assert_ne!(crack(plain).unwrap().0, 0);
+    }
+}
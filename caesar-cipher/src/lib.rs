@@ -3,22 +3,57 @@ This code defines two functions: encrypt and decrypt.
 The encrypt function takes a plaintext string and a shift value, and returns the ciphertext string. The decrypt function takes a ciphertext string and a shift value,
 and returns the plaintext string.
 
+Both are thin wrappers around the Caesar implementation of the Cipher trait,
+see the cipher module for ROT13, Atbash, Affine and Vigenere. Any shift works,
+it is reduced modulo 26; use Caesar::with_alphabet to rotate accented letters.
+
+encrypt_stream and decrypt_stream do the same from a reader to a writer in
+buffered chunks, for inputs too large to hold in memory. crack recovers the
+shift from the letter frequencies of a ciphertext.
+
+This is the shared cipher library of the workspace: caesar-cipher-cli and
+decoder-ring depend on it instead of carrying their own copy.
 */
 
+pub mod alphabet;
+pub mod cipher;
+pub mod crack;
+pub mod stream;
+
+use std::io::{self, Read, Write};
+
+pub use alphabet::Alphabet;
+pub use cipher::{Affine, Atbash, Caesar, Cipher, Rot13, Vigenere};
+pub use crack::crack;
+
 pub fn encrypt(text: &str, shift: u8) -> String {
-    let mut result = String::new();
-    for c in text.chars() {
-        if c.is_ascii_alphabetic() {
-            let base = if c.is_ascii_lowercase() { b'a' } else { b'A' };
-            let offset = (c as u8 - base + shift) % 26;
-            result.push((base + offset) as char);
-        } else {
-            result.push(c);
-        }
-    }
-    result
+    Caesar::new(shift as usize).encrypt(text)
 }
 
 pub fn decrypt(text: &str, shift: u8) -> String {
-    encrypt(text, 26 - shift)
+    Caesar::new(shift as usize).decrypt(text)
+}
+
+pub fn encrypt_stream<R: Read, W: Write>(reader: R, writer: W, shift: u8) -> io::Result<()> {
+    let caesar = Caesar::new(shift as usize);
+    stream::transform(reader, writer, |chunk| caesar.encrypt(chunk))
+}
+
+pub fn decrypt_stream<R: Read, W: Write>(reader: R, writer: W, shift: u8) -> io::Result<()> {
+    let caesar = Caesar::new(shift as usize);
+    stream::transform(reader, writer, |chunk| caesar.decrypt(chunk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_shift() {
+        assert_eq!(encrypt("xyz", 3), "abc");
+        assert_eq!(encrypt("xyz", 29), "abc");
+        assert_eq!(encrypt("Hello", 0), "Hello");
+        assert_eq!(decrypt("abc", 25), encrypt("abc", 1));
+        assert_eq!(decrypt(&encrypt("Hello", 200), 200), "Hello");
+    }
 }
@@ -0,0 +1,100 @@
+/*
+Run a cipher over a reader in fixed-size chunks, so files of any size are
+rotated with constant memory.
+
+A chunk never ends in the middle of a UTF-8 character: the incomplete bytes
+are carried over to the next read. Input that is not valid UTF-8 is an
+InvalidData error.
+
+Each chunk is encrypted on its own, so only ciphers that treat every
+character independently (Caesar, ROT13, Atbash, Affine) can be streamed.
+Vigenere would restart its keyword at every chunk.
+*/
+
+use std::io::{self, Read, Write};
+use std::str;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Transform reader into writer chunk by chunk with f
+pub fn transform<R, W, F>(reader: R, writer: W, f: F) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: Fn(&str) -> String,
+{
+    transform_chunks(reader, writer, CHUNK_SIZE, f)
+}
+
+fn transform_chunks<R, W, F>(
+    mut reader: R,
+    mut writer: W,
+    chunk_size: usize,
+    f: F,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: Fn(&str) -> String,
+{
+    // A UTF-8 character is at most 4 bytes, so 3 carried bytes always fit
+    let mut buf = vec![0; chunk_size + 3];
+    let mut carried = 0;
+    loop {
+        let n = match reader.read(&mut buf[carried..]) {
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let filled = carried + n;
+        if n == 0 {
+            if carried > 0 {
+                return Err(invalid_utf8());
+            }
+            return writer.flush();
+        }
+
+        let valid = match str::from_utf8(&buf[..filled]) {
+            Ok(text) => text,
+            // An incomplete character at the end, keep it for the next read
+            Err(err) if err.error_len().is_none() => {
+                str::from_utf8(&buf[..err.valid_up_to()]).unwrap()
+            }
+            Err(_) => return Err(invalid_utf8()),
+        };
+        writer.write_all(f(valid).as_bytes())?;
+
+        let consumed = valid.len();
+        buf.copy_within(consumed..filled, 0);
+        carried = filled - consumed;
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "stream is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Alphabet, Caesar, Cipher};
+
+    #[test]
+    fn test_chunks_never_split_characters() {
+        let text = "Não há coração que não pulse, ação!";
+        let caesar = Caesar::with_alphabet(5, Alphabet::portuguese());
+        for chunk_size in [1, 2, 3, 7, 1024] {
+            let mut out = Vec::new();
+            transform_chunks(text.as_bytes(), &mut out, chunk_size, |chunk| {
+                caesar.encrypt(chunk)
+            })
+            .unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), caesar.encrypt(text));
+        }
+
+        let err = transform(&b"ok \xff"[..], Vec::new(), str::to_string).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = transform(&b"cut \xc3"[..], Vec::new(), str::to_string).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,212 @@
+/*
+Word lists to check candidate plaintexts against.
+
+A word list has one word per line, in any case. Entries with anything but
+the letters a-z, such as "don't" or "café", are skipped, since the ciphers
+only shift a-z.
+
+A text with spaces is scored by the fraction of its words in the list. A
+text whose spaces were stripped is first segmented: split into the word
+sequence that covers the most letters with listed words, with as few words
+as possible. Letters no word covers are kept together as one unknown word,
+and the score is again the fraction of listed words. One-letter entries are
+left out of segmentation, because most lists have every letter as a word
+and any text would split into single letters.
+*/
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dictionary {
+    words: HashSet<String>,
+    // Length of the longest word, the most letters one segment can take
+    max_len: usize,
+}
+
+// The best split of the letters before a position, and the step that reached it
+#[derive(Debug, Clone, Copy)]
+struct Split {
+    // Letters covered by listed words
+    covered: usize,
+    words: usize,
+    // Where the last segment starts, and whether it is a listed word
+    start: usize,
+    from_known: bool,
+}
+
+impl Split {
+    fn beats(&self, other: &Option<Split>) -> bool {
+        other.is_none_or(|other| (self.covered, other.words) > (other.covered, self.words))
+    }
+}
+
+impl Dictionary {
+    // One word per line
+    pub fn parse(contents: &str) -> Dictionary {
+        let words: HashSet<String> = contents
+            .lines()
+            .map(|line| line.trim().to_ascii_lowercase())
+            .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase()))
+            .collect();
+        let max_len = words.iter().map(String::len).max().unwrap_or(0);
+        Dictionary { words, max_len }
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Dictionary> {
+        Ok(Dictionary::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // Whether a word is listed, ignoring case
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&word.to_ascii_lowercase())
+    }
+
+    // Split the letters of text into the most likely words, all lowercase
+    pub fn segment(&self, text: &str) -> Vec<String> {
+        let letters: String = text
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        let n = letters.len();
+
+        // best[i][1] ends the first i letters with a listed word, best[i][0] with an unknown letter
+        let mut best: Vec<[Option<Split>; 2]> = vec![[None, None]; n + 1];
+        best[0][1] = Some(Split {
+            covered: 0,
+            words: 0,
+            start: 0,
+            from_known: true,
+        });
+        for i in 0..n {
+            for known in [false, true] {
+                let Some(here) = best[i][known as usize] else {
+                    continue;
+                };
+                // An unknown letter starts a new word unless it extends an unknown one
+                let unknown = Split {
+                    covered: here.covered,
+                    words: here.words + known as usize,
+                    start: i,
+                    from_known: known,
+                };
+                if unknown.beats(&best[i + 1][0]) {
+                    best[i + 1][0] = Some(unknown);
+                }
+                for end in i + 2..=n.min(i + self.max_len) {
+                    if !self.words.contains(&letters[i..end]) {
+                        continue;
+                    }
+                    let word = Split {
+                        covered: here.covered + end - i,
+                        words: here.words + 1,
+                        start: i,
+                        from_known: known,
+                    };
+                    if word.beats(&best[end][1]) {
+                        best[end][1] = Some(word);
+                    }
+                }
+            }
+        }
+
+        // Walk back from the end, merging runs of unknown letters
+        let mut words = Vec::new();
+        let mut end = n;
+        let mut known = match best[n] {
+            [Some(unknown), Some(word)] => !unknown.beats(&Some(word)),
+            [_, word] => word.is_some(),
+        };
+        let mut unknown_end = None;
+        while end > 0 {
+            let split = best[end][known as usize].unwrap();
+            if known {
+                words.push(letters[split.start..end].to_string());
+            } else {
+                unknown_end.get_or_insert(end);
+                if split.from_known {
+                    words.push(letters[split.start..unknown_end.take().unwrap()].to_string());
+                }
+            }
+            end = split.start;
+            known = split.from_known;
+        }
+        words.reverse();
+        words
+    }
+
+    // Fraction of the words of text that are listed, 0 without letters
+    pub fn score(&self, text: &str) -> f64 {
+        let words: Vec<String> = if text.trim().contains(char::is_whitespace) {
+            text.split(|c: char| !c.is_ascii_alphabetic())
+                .filter(|word| !word.is_empty())
+                .map(str::to_ascii_lowercase)
+                .collect()
+        } else {
+            self.segment(text)
+        };
+        if words.is_empty() {
+            return 0.0;
+        }
+        let listed = words
+            .iter()
+            .filter(|word| self.words.contains(*word))
+            .count();
+        listed as f64 / words.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &str =
+        "a\nb\nc\noff\nof\nto\nthe\nbun\nbunk\nbunker\nevery\nperson\nfor\nthem\nselves\n\
+        themselves\ndon't\n";
+
+    #[test]
+    fn test_segment() {
+        let dictionary = Dictionary::parse(WORDS);
+        assert_eq!(dictionary.len(), 16);
+        assert!(dictionary.contains("Bunker"));
+        assert_eq!(
+            dictionary.segment("Offtothebunker.Everypersonforthemselves"),
+            vec![
+                "off",
+                "to",
+                "the",
+                "bunker",
+                "every",
+                "person",
+                "for",
+                "themselves"
+            ]
+        );
+        assert_eq!(
+            dictionary.segment("xqtothezzbunker"),
+            vec!["xq", "to", "the", "zz", "bunker"]
+        );
+        assert!(dictionary.segment("42").is_empty());
+    }
+
+    #[test]
+    fn test_score() {
+        let dictionary = Dictionary::parse(WORDS);
+        assert_eq!(dictionary.score("Off to the bunker"), 1.0);
+        assert_eq!(dictionary.score("Off to the moon"), 0.75);
+        assert_eq!(dictionary.score("offtothemoon"), 0.75);
+        assert_eq!(dictionary.score("abc"), 0.0);
+        assert_eq!(dictionary.score(""), 0.0);
+    }
+}
@@ -0,0 +1,201 @@
+/*
+Letter frequency models of plaintext languages.
+
+A model is read from a frequency file with one letter and its frequency per
+line. Lines starting with # are comments, and the first comment names the
+language; without one the file name is used. Letters that are missing count
+as 0, and the frequencies are scaled to add up to 100:
+
+    # Portuguese
+    a 14.634
+    b 1.043
+    ...
+
+English, Portuguese, Spanish, French and German are built in, from the
+files in data/. Accented letters are not part of the 26-letter tables, since
+shift ciphers pass them through unchanged.
+
+A model can also carry a word list of its language, which guess_shift uses
+to break near ties between the best candidates.
+*/
+
+use crate::dictionary::Dictionary;
+use caesar_cipher::crack;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const BUILT_IN: [(&str, &str); 5] = [
+    ("en", include_str!("../data/en.freq")),
+    ("pt", include_str!("../data/pt.freq")),
+    ("es", include_str!("../data/es.freq")),
+    ("fr", include_str!("../data/fr.freq")),
+    ("de", include_str!("../data/de.freq")),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageModel {
+    code: String,
+    name: String,
+    // Percent of a-z, adds up to 100
+    frequencies: [f64; 26],
+    dictionary: Option<Dictionary>,
+}
+
+impl LanguageModel {
+    // Parse the contents of a frequency file, code names the model when it has no comment
+    pub fn parse(code: &str, contents: &str) -> Result<LanguageModel, String> {
+        let mut name = None;
+        let mut frequencies = [0.0; 26];
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix('#') {
+                name.get_or_insert_with(|| comment.trim().to_string());
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: expected a letter and a frequency", number + 1);
+            let mut fields = line.split_whitespace();
+            let (Some(letter), Some(frequency), None) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            let letter = match letter.to_ascii_lowercase().as_bytes() {
+                [c @ b'a'..=b'z'] => (c - b'a') as usize,
+                _ => return Err(invalid()),
+            };
+            frequencies[letter] = frequency
+                .parse::<f64>()
+                .ok()
+                .filter(|f| f.is_finite() && *f >= 0.0)
+                .ok_or_else(invalid)?;
+        }
+
+        let total: f64 = frequencies.iter().sum();
+        if total == 0.0 {
+            return Err(format!("no letter frequencies for {}", code));
+        }
+        for frequency in &mut frequencies {
+            *frequency *= 100.0 / total;
+        }
+        Ok(LanguageModel {
+            code: code.to_string(),
+            name: name.unwrap_or_else(|| code.to_string()),
+            frequencies,
+            dictionary: None,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<LanguageModel> {
+        let contents = fs::read_to_string(path)?;
+        let code = path
+            .file_stem()
+            .map_or("custom".into(), |stem| stem.to_string_lossy());
+        LanguageModel::parse(&code, &contents).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+
+    // All built-in models, English first, parsed once
+    pub fn built_in() -> &'static [LanguageModel] {
+        static MODELS: OnceLock<Vec<LanguageModel>> = OnceLock::new();
+        MODELS.get_or_init(|| {
+            BUILT_IN
+                .iter()
+                .map(|(code, contents)| LanguageModel::parse(code, contents).unwrap())
+                .collect()
+        })
+    }
+
+    // A built-in model by its code: en, pt, es, fr or de
+    pub fn by_code(code: &str) -> Option<LanguageModel> {
+        LanguageModel::built_in()
+            .iter()
+            .find(|model| model.code == code)
+            .cloned()
+    }
+
+    pub fn english() -> LanguageModel {
+        LanguageModel::by_code("en").unwrap()
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // The same model with a word list of the language
+    pub fn with_dictionary(self, dictionary: Dictionary) -> LanguageModel {
+        LanguageModel {
+            dictionary: Some(dictionary),
+            ..self
+        }
+    }
+
+    pub fn dictionary(&self) -> Option<&Dictionary> {
+        self.dictionary.as_ref()
+    }
+
+    // Percent of each letter a-z
+    pub fn frequencies(&self) -> &[f64; 26] {
+        &self.frequencies
+    }
+
+    // Chance that two letters of the language picked at random are the same
+    pub fn index_of_coincidence(&self) -> f64 {
+        self.frequencies.iter().map(|f| (f / 100.0).powi(2)).sum()
+    }
+
+    // Chi-squared distance between the letters of text and this language, None without letters
+    pub fn chi_squared(&self, text: &str) -> Option<f64> {
+        crack::chi_squared(text, &self.frequencies)
+    }
+}
+
+impl Default for LanguageModel {
+    fn default() -> Self {
+        LanguageModel::english()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frequency_file() {
+        let model = LanguageModel::parse("xx", "# Test\na 3\nB 1\n\n").unwrap();
+        assert_eq!(model.name(), "Test");
+        assert_eq!(model.frequencies()[0], 75.0);
+        assert_eq!(model.frequencies()[1], 25.0);
+        assert_eq!(LanguageModel::parse("xx", "a 1\n").unwrap().name(), "xx");
+
+        assert!(LanguageModel::parse("xx", "ab 3").is_err());
+        assert!(LanguageModel::parse("xx", "a lots").is_err());
+        assert!(LanguageModel::parse("xx", "# only a comment").is_err());
+    }
+
+    #[test]
+    fn test_built_in_models() {
+        let names: Vec<String> = LanguageModel::built_in()
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["English", "Portuguese", "Spanish", "French", "German"]
+        );
+        let total: f64 = LanguageModel::english().frequencies().iter().sum();
+        assert!((total - 100.0).abs() < 1e-9);
+    }
+}
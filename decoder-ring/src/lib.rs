@@ -1,115 +1,218 @@
-use std::collections::HashMap;
-
-fn gen_counts() -> HashMap<char, f32> {
-    // Reference letter frequencies in English
-    let mut eng_freq: HashMap<char, f32> = HashMap::new();
-
-    // Accounts for 80% of all letters in English
-    eng_freq.insert('e', 12.7);
-    eng_freq.insert('t', 9.1);
-    eng_freq.insert('a', 8.2);
-    eng_freq.insert('o', 7.5);
-    eng_freq.insert('i', 7.0);
-    eng_freq.insert('n', 6.7);
-    eng_freq.insert('s', 6.3);
-    eng_freq.insert('h', 6.1);
-    eng_freq.insert('r', 6.0);
-    eng_freq.insert('d', 4.3);
-
-    eng_freq
+pub mod dictionary;
+pub mod language;
+pub mod ngram;
+pub mod scoring;
+pub mod stats;
+pub mod substitution;
+pub mod vigenere;
+
+pub use dictionary::Dictionary;
+pub use language::LanguageModel;
+pub use scoring::Scoring;
+
+use serde::Serialize;
+
+// How many of the best scored candidates the word list may reorder
+const DICTIONARY_CONTENDERS: usize = 3;
+
+// Print the frequency analysis report of text, see stats
+pub fn print_stats_analysis(text: &str, model: &LanguageModel) {
+    println!("{}", stats::analyse(text, model));
 }
 
-fn stats_analysis(text: &str) -> Vec<(char, u32, f32, Option<f32>, f32)> {
-    let mut counts: HashMap<char, u32> = HashMap::new();
-
-    for c in text.chars() {
-        *counts.entry(c).or_insert(0) += 1;
-    }
-
-    let total: u32 = counts.values().sum();
-
-    let eng_freq_map = gen_counts();
-    let eng_freq_map: HashMap<char, f32> = eng_freq_map.iter().map(|(k, v)| (*k, *v)).collect();
-
-    let mut results = Vec::new();
+// Shifts every letter forward by shift, so a message encrypted with shift s
+// is decrypted with 26 - s
+pub fn decrypt(text: &str, shift: u8) -> String {
+    caesar_cipher::encrypt(text, shift)
+}
 
-    for (letter, count) in &counts {
-        let freq = (*count as f32 / total as f32) * 100.0;
-        let eng_freq = eng_freq_map.get(&letter.to_ascii_lowercase()).cloned();
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candidate {
+    pub shift: u8,
+    // Higher is better, negative infinity (null in JSON) without letters
+    pub score: f64,
+    pub plaintext: String,
+    // Fraction of dictionary words, when the model has a word list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<f64>,
+    // The plaintext split into words, when it has no spaces and the model has a word list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segmented: Option<String>,
+}
 
-        let eng_freq_diff = eng_freq.map_or(0.0, |f| (freq - f).abs());
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GuessResult {
+    // Name of the language model the candidates were scored against
+    pub language: String,
+    pub scoring: Scoring,
+    // Number of shifts tried
+    pub depth: u8,
+    // One per shift, best first, ties in shift order
+    pub candidates: Vec<Candidate>,
+}
 
-        results.push((*letter, *count, freq, eng_freq, eng_freq_diff));
+impl GuessResult {
+    // The most likely candidate, None when no shift was tried
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates.first()
     }
-    results
-}
 
-pub fn print_stats_analysis(text: &str) {
-    let stats = stats_analysis(text);
-    for (letter, count, freq, eng_freq, eng_freq_diff) in stats {
-        println!(
-            "{}: {} ({}%), English Freq: {} ({}%)",
-            letter,
-            count,
-            freq,
-            eng_freq.unwrap_or(0.0),
-            eng_freq_diff
-        );
+    // The n best candidates, or all of them when there are fewer
+    pub fn top(&self, n: usize) -> &[Candidate] {
+        &self.candidates[..n.min(self.candidates.len())]
     }
-}
 
-pub fn decrypt(text: &str, shift: u8) -> String {
-    let mut result = String::new();
-
-    for c in text.chars() {
-        if c.is_ascii_alphabetic() {
-            let base = if c.is_ascii_lowercase() { b'a' } else { b'A' };
-            let offset = (c as u8 - base + shift) % 26;
-            result.push((base + offset) as char);
-        } else {
-            result.push(c);
-        }
+    // Keep only the n best candidates
+    pub fn truncate(&mut self, n: usize) {
+        self.candidates.truncate(n);
     }
 
-    result
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
 }
 
 /*
 Guess Shift:
 
-First, uses statistical analysis to determine the most likely shift.
-Then, uses the most likely shift to decrypt the message.
+First, scores the message decrypted with every shift, by default with the
+chi-squared distance of its letter frequencies to the language model.
+Then, ranks the candidates from the best score to the worst.
+When the model has a word list, the best few candidates are ranked again by
+the fraction of dictionary words, since a close wrong shift can score about
+as well on letter frequencies alone. Equal fractions keep the score order.
 Accepts:
  * text: the message to decrypt
  * depth: the number of shifts to try
-Returns:
-   * depth: the number of shifts to tried
-   * shift: the most likely shift
-   * decrypted: the decrypted message
+ * scoring: chi-squared, bigram or quadgram (guess_shift_with only)
+ * model: the language of the plaintext, n-gram scoring is English only
+Returns a GuessResult with every shift, its score and decrypted message.
 */
 
-pub fn guess_shift(text: &str, depth: u8) -> (u8, u8, String, f32) {
-    let mut max_score = 0.0;
-    let mut best_shift = 0;
-    let mut decrypted = String::new();
-
-    for shift in 0..depth {
-        let decrypted_text = decrypt(text, shift);
-        let stats = stats_analysis(&decrypted_text);
+pub fn guess_shift(text: &str, depth: u8, model: &LanguageModel) -> GuessResult {
+    guess_shift_with(text, depth, Scoring::ChiSquared, model)
+}
 
-        let mut score = 0.0;
-        for (_, _, freq, eng_freq, eng_freq_diff) in stats {
-            if let Some(eng_freq) = eng_freq {
-                score += (1.0 - eng_freq_diff / eng_freq) * freq;
+pub fn guess_shift_with(
+    text: &str,
+    depth: u8,
+    scoring: Scoring,
+    model: &LanguageModel,
+) -> GuessResult {
+    let mut candidates: Vec<Candidate> = (0..depth)
+        .map(|shift| {
+            let plaintext = decrypt(text, shift);
+            let dictionary = model.dictionary();
+            let segmented = dictionary
+                .filter(|_| !plaintext.trim().contains(char::is_whitespace))
+                .map(|dictionary| dictionary.segment(&plaintext).join(" "));
+            Candidate {
+                shift,
+                score: scoring.score(&plaintext, model),
+                words: dictionary.map(|dictionary| dictionary.score(&plaintext)),
+                segmented,
+                plaintext,
             }
+        })
+        .collect();
+    // Stable, so equal scores stay in shift order
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    if model.dictionary().is_some() {
+        let contenders = DICTIONARY_CONTENDERS.min(candidates.len());
+        candidates[..contenders].sort_by(|a, b| b.words.unwrap().total_cmp(&a.words.unwrap()));
+    }
+
+    GuessResult {
+        language: model.name().to_string(),
+        scoring,
+        depth,
+        candidates,
+    }
+}
+
+/*
+Detect Language:
+
+Guesses the shift with chi-squared scoring against every model, and keeps
+the language whose best candidate fits it most closely.
+None when there are no models or no shifts to try.
+*/
+
+pub fn detect_language(text: &str, depth: u8, models: &[LanguageModel]) -> Option<GuessResult> {
+    models
+        .iter()
+        .map(|model| guess_shift(text, depth, model))
+        .filter_map(|result| Some((result.best()?.score, result)))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, result)| result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_shift_short_message() {
+        let plain = "meet me at the old bridge";
+        let cipher = caesar_cipher::encrypt(plain, 7);
+        let english = LanguageModel::english();
+        let result = guess_shift_with(&cipher, 26, Scoring::Quadgram, &english);
+        let best = result.best().unwrap();
+        assert_eq!((best.shift, best.plaintext.as_str()), (19, plain));
+
+        let long = "Ypp dy dro lexuob. Ofobi zobcyx pyb drowcovfoc";
+        for scoring in Scoring::ALL {
+            let result = guess_shift_with(long, 26, scoring, &english);
+            assert_eq!(result.best().unwrap().shift, 16, "{}", scoring);
         }
-        println!("Shift: {}, Score: {}", shift, score);
-        if score > max_score {
-            max_score = score;
-            best_shift = shift;
-            decrypted = decrypted_text;
-        }
     }
 
-    (depth, best_shift, decrypted, max_score)
+    #[test]
+    fn test_detect_language() {
+        let models = LanguageModel::built_in();
+        let plain = "o rato roeu a roupa do rei de roma, e a rainha com raiva resolveu \
+            remendar o manto que ele usava nas festas da corte";
+        let cipher = caesar_cipher::encrypt(plain, 5);
+        let result = detect_language(&cipher, 26, models).unwrap();
+        assert_eq!(result.language, "Portuguese");
+        let best = result.best().unwrap();
+        assert_eq!((best.shift, best.plaintext.as_str()), (21, plain));
+    }
+
+    #[test]
+    fn test_guess_result_ranks_every_shift() {
+        let result = guess_shift("Lipps asvph", 26, &LanguageModel::english());
+        assert_eq!(result.candidates.len(), 26);
+        assert!(result
+            .candidates
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+        assert_eq!(result.top(3), &result.candidates[..3]);
+        assert_eq!(result.top(100).len(), 26);
+        assert_eq!(
+            guess_shift("Lipps", 0, &LanguageModel::english()).best(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dictionary_breaks_near_ties() {
+        // Two short words have too few letters for frequencies to pick the right shift
+        let cipher = caesar_cipher::encrypt("stay low", 7);
+        let english = LanguageModel::english();
+        let result = guess_shift(&cipher, 26, &english);
+        assert_eq!(result.best().unwrap().plaintext, "hipn adl");
+        assert_eq!(result.best().unwrap().words, None);
+
+        let english = english.with_dictionary(Dictionary::parse("stay\nlow\n"));
+        let result = guess_shift(&cipher, 26, &english);
+        let best = result.best().unwrap();
+        assert_eq!((best.shift, best.words), (19, Some(1.0)));
+        assert_eq!(best.plaintext, "stay low");
+
+        let stripped = caesar_cipher::encrypt("staylow", 7);
+        let result = guess_shift(&stripped, 26, &english);
+        let best = result.best().unwrap();
+        assert_eq!(best.segmented.as_deref(), Some("stay low"));
+    }
 }
@@ -7,41 +7,102 @@ This is a shift 16 message: "Off to the bunker. Every person for themselves"
 
 cargo run -- --message "Ypp dy dro lexuob. Ofobi zobcyx pyb drowcovfoc" --guess
 
-Here is an example of it in action:
-
-Shift: 0, Score: 7.538945
-Shift: 1, Score: 10.078025
-Shift: 2, Score: 20.755177
-Shift: 3, Score: 11.284368
-Shift: 4, Score: 7.5232525
-Shift: 5, Score: 23.558884
-Shift: 6, Score: 21.086407
-Shift: 7, Score: 9.926911
-Shift: 8, Score: 5.5866623
-Shift: 9, Score: 15.310673
-Shift: 10, Score: 17.950832
-Shift: 11, Score: 21.200842
-Shift: 12, Score: 23.447578
-Shift: 13, Score: 14.035946
-Shift: 14, Score: 13.314641
-Shift: 15, Score: 2.055822
-Shift: 16, Score: 40.54977
-Shift: 17, Score: 15.98934
-Shift: 18, Score: 18.178614
-Shift: 19, Score: 8.523561
-Shift: 20, Score: 9.371011
-Shift: 21, Score: 12.385875
-Shift: 22, Score: 8.159725
-Shift: 23, Score: 10.439689
-Shift: 24, Score: 17.104122
-Shift: 25, Score: 14.300304
-Best shift: 16 (out of 26), score: 40.54977
-Decrypted message: Off to the bunker. Every person for themselves
+Here is an example of it in action, the five best guesses first:
+
+Language: English, scoring: chi-squared, shifts tried: 26
+Rank  Shift       Score  Decrypted message
+   1     16    -29.7061  Off to the bunker. Every person for themselves
+   2      3   -111.3487  Bss gb gur ohaxre. Rirel crefba sbe gurzfryirf
+   3      5   -132.5776  Duu id iwt qjcztg. Tktgn etghdc udg iwtbhtakth
+   4     17   -166.8742  Pgg up uif cvolfs. Fwfsz qfstpo gps uifntfmwft
+   5      4   -170.0868  Ctt hc hvs pibysf. Sjsfm dsfgcb tcf hvsagszjsg
+
+--top sets how many guesses are shown.
+
+Scores are higher for better guesses. The default chi-squared scoring compares
+the letter frequencies of each guess with English; short messages crack more
+reliably with n-gram scoring, the log probability of every run of letters:
+
+cargo run -- --message "Lipps asvph" --guess --scoring quadgram
+
+Chi-squared scoring works for other languages too; the bigram and quadgram
+tables are English only. --lang picks one of the built-in letter
+frequencies (en, pt, es, fr, de), --frequencies loads them from a file, and
+--lang auto tries every built-in language and reports the one that fits
+best with chi-squared scoring, the only scoring it accepts:
+
+cargo run -- --message "T wfyt wtjz f wtzuf it wjn ij Wtrf" --guess --lang auto --top 1
+
+Language: Portuguese, scoring: chi-squared, shifts tried: 26
+Rank  Shift       Score  Decrypted message
+   1     21    -18.7214  O rato roeu a roupa do rei de Roma
+
+--format json prints the same candidates as JSON, for other tools to read.
+
+Letter frequencies of a short message can favour a wrong shift. --words loads
+a word list of the language, one word per line, and the three best guesses
+are ranked again by the share of their words that are in it. Messages with
+the spaces stripped are split into words first:
+
+printf "stay\nlow\n" > words.txt
+cargo run -- --message "zahfsvd" --guess --words words.txt --top 3
+
+Language: English, scoring: chi-squared, shifts tried: 26
+Rank  Shift       Score  Words  Decrypted message
+   1     19    -17.1798   100%  stay low
+   2      8    -15.6966     0%  hipnadl
+   3     12    -17.4893     0%  lmtrehp
+
+--stats prints a frequency analysis of the letters, against the language of
+--lang, and a hint of which kind of cipher was used:
+
+cargo run -- --message "Ypp dy dro lexuob. Ofobi zobcyx pyb drowcovfoc, dro cdybw sc mywsxq dyxsqrd" --stats
+
+Letters: 61
+Index of coincidence: 0.0661 (English 0.0655, random 0.0385)
+Entropy: 3.88 bits per letter (English 4.18, random 4.70)
+
+Letter  Count  Message                                 English
+o           9   14.75% ##############################    7.49% ###############
+d           7   11.48% #######################           4.29% #########
+y           7   11.48% #######################           2.00% ####
+...
+t           0    0.00%                                   9.09% ##################
+
+Top bigrams: dr 3, dy 3, ob 3, ro 3, cd 2, fo 2, oc 2, ws 2, yb 2, yx 2
+Top trigrams: dro 3, ocd 2, bcy 1, bdr 1, biz 1, bof 1, bws 1, cdr 1, cdy 1, cmy 1
+Likely cipher: a shift cipher such as Caesar, try --guess
+
+A Vigenere cipher is cracked by estimating the key length, then cracking
+each key letter as its own shift:
+
+cargo run -- --message "Zjr hb elq phyoqf. Rgidm cpveca qsd hupqesygie, hup wfcex me qbxmzu gzruuue ezr gsi dcnow iwyw gxcfp fqtbci pojy wa petrs kneid oao fxoavifg" --vigenere
+
+Key: lemon, confidence: 0.63
+Decrypted message: Off to the bunker. Every person for themselves, the storm is coming tonight and the roads will close before dawn so bring water and blankets
+
+Any other substitution cipher is solved by hill-climbing over swaps of the
+key, with random restarts, until --restarts or --time-budget seconds run out:
+
+cargo run --release -- --message "Zit jxqkztksn kthgkz vql sqzt quqof wteqxlt zit tbhgkz ykgd zit wossofu lnlztd lzghhtr iqsyvqn zikgxui zit fouiz. Fgwgrn fgzoetr xfzos zit dgkfofu, vitf zit rqliwgqkrl ligvtr iqsy gy zit xlxqs ktctfxt." --substitution
+
+Cipher: abcdefghijklmnopqrstuvwxyz
+Key:    jkvmcnophqrsxyizadlegwbuft
+Fitness: -1787.94 after 101 climbs
+Decrypted message: The quarterly report was late again because the ekport from the billing system stopped halfway through the night. Nobody noticed until the morning, when the dashboards showed half of the usual revenue.
 
 */
 
-use clap::Parser;
-use decoder_ring::print_stats_analysis;
+use clap::{Parser, ValueEnum};
+use decoder_ring::substitution::{solve_substitution, SolverConfig};
+use decoder_ring::vigenere::crack_vigenere;
+use decoder_ring::{
+    detect_language, print_stats_analysis, Dictionary, GuessResult, LanguageModel, Scoring,
+};
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 
 /// CLI tool to reverse engineer a Caesar cipher
 #[derive(Parser, Debug)]
@@ -58,22 +119,181 @@ struct Args {
     //guess the shift
     #[arg(short, long)]
     guess: bool,
+
+    /// How to score each guess: chi-squared, bigram or quadgram.
+    /// Bigram and quadgram scoring is always against English, whatever --lang is
+    #[arg(long, default_value_t = Scoring::ChiSquared)]
+    scoring: Scoring,
+
+    /// Number of best guesses to show
+    #[arg(long, default_value = "5")]
+    top: usize,
+
+    /// Output format of the guesses
+    #[arg(short, long, value_enum, default_value = "table")]
+    format: Format,
+
+    /// Language of the message: en, pt, es, fr, de or auto to detect it
+    #[arg(long, default_value = "en")]
+    lang: String,
+
+    /// File of letter frequencies to use instead of a built-in language
+    #[arg(long, conflicts_with = "lang")]
+    frequencies: Option<PathBuf>,
+
+    /// Word list of the language, one word per line, to break near ties
+    #[arg(long)]
+    words: Option<PathBuf>,
+
+    /// Crack a Vigenere cipher instead of a single shift
+    #[arg(long)]
+    vigenere: bool,
+
+    /// Longest Vigenere key to consider
+    #[arg(long, default_value = "20")]
+    max_key_length: usize,
+
+    /// Solve a general substitution cipher by hill-climbing
+    #[arg(long)]
+    substitution: bool,
+
+    /// Number of random restarts of the substitution solver
+    #[arg(long, default_value = "100")]
+    restarts: usize,
+
+    /// Seconds the substitution solver may run
+    #[arg(long, default_value = "10", value_parser = parse_seconds)]
+    time_budget: Duration,
+
+    /// Seed of the random restarts
+    #[arg(long, default_value = "0")]
+    seed: u64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Table,
+    Json,
+}
+
+// A non-negative, finite number of seconds
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|err| format!("{}", err))?;
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("{}", err))
+}
+
+// The model chosen with --frequencies or --lang and --words, None to detect the language
+fn language_model(args: &Args) -> Result<Option<LanguageModel>, Box<dyn Error>> {
+    let model = if let Some(path) = &args.frequencies {
+        LanguageModel::from_file(path)?
+    } else if args.lang == "auto" {
+        if args.words.is_some() {
+            return Err("--words needs a language, not --lang auto".into());
+        }
+        // Languages are told apart by their letter frequencies alone
+        if args.scoring != Scoring::ChiSquared {
+            return Err("--lang auto always scores with chi-squared, drop --scoring".into());
+        }
+        return Ok(None);
+    } else {
+        LanguageModel::by_code(&args.lang).ok_or_else(|| {
+            format!(
+                "unknown language '{}', expected en, pt, es, fr, de or auto",
+                args.lang
+            )
+        })?
+    };
+    match &args.words {
+        Some(path) => Ok(Some(model.with_dictionary(Dictionary::from_file(path)?))),
+        None => Ok(Some(model)),
+    }
+}
+
+fn print_guesses(result: &GuessResult, format: Format) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Table => {
+            println!(
+                "Language: {}, scoring: {}, shifts tried: {}",
+                result.language, result.scoring, result.depth
+            );
+            println!(
+                "{:>4}  {:>5}  {:>10}  {:>5}  Decrypted message",
+                "Rank", "Shift", "Score", "Words"
+            );
+            for (rank, candidate) in result.candidates.iter().enumerate() {
+                let words = candidate
+                    .words
+                    .map_or("-".to_string(), |words| format!("{:.0}%", words * 100.0));
+                println!(
+                    "{:>4}  {:>5}  {:>10.4}  {:>5}  {}",
+                    rank + 1,
+                    candidate.shift,
+                    candidate.score,
+                    words,
+                    candidate.segmented.as_ref().unwrap_or(&candidate.plaintext)
+                );
+            }
+        }
+        Format::Json => println!("{}", result.to_json()?),
+    }
+    Ok(())
 }
 
 // run it
 fn main() {
     let args = Args::parse();
+    let model = language_model(&args).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
     //stats
     if args.stats {
-        print_stats_analysis(&args.message);
+        print_stats_analysis(
+            &args.message,
+            model.as_ref().unwrap_or(&LanguageModel::english()),
+        );
     }
     //guess
     if args.guess {
-        let (depth, best_shift, decrypted, max_score) = decoder_ring::guess_shift(&args.message, 26);
-        println!(
-            "Best shift: {} (out of {}), score: {}",
-            best_shift, depth, max_score
-        );
-        println!("Decrypted message: {}", decrypted);        
+        let mut result = match &model {
+            Some(model) => decoder_ring::guess_shift_with(&args.message, 26, args.scoring, model),
+            None => detect_language(&args.message, 26, LanguageModel::built_in())
+                .expect("there are built-in languages"),
+        };
+        result.truncate(args.top);
+        if let Err(err) = print_guesses(&result, args.format) {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+    //vigenere
+    if args.vigenere {
+        match crack_vigenere(&args.message, args.max_key_length) {
+            Some(guess) => {
+                println!("Key: {}, confidence: {:.2}", guess.key, guess.confidence);
+                println!("Decrypted message: {}", guess.plaintext);
+            }
+            None => println!("The message has no letters to analyse"),
+        }
+    }
+    //substitution
+    if args.substitution {
+        let config = SolverConfig {
+            restarts: args.restarts,
+            time_budget: args.time_budget,
+            seed: args.seed,
+        };
+        match solve_substitution(&args.message, &config) {
+            Some(solution) => {
+                println!("Cipher: abcdefghijklmnopqrstuvwxyz");
+                println!("Key:    {}", solution.key);
+                println!(
+                    "Fitness: {:.2} after {} climbs",
+                    solution.fitness, solution.climbs
+                );
+                println!("Decrypted message: {}", solution.plaintext);
+            }
+            None => println!("The message has no letters to analyse"),
+        }
     }
 }
@@ -0,0 +1,100 @@
+/*
+Log-probability n-gram models of English.
+
+The counts come from data/english.txt, a sample of public domain English
+prose embedded in the binary. A text is scored with the sum of the log10
+probabilities of its overlapping n-grams, over its letters only, so spaces
+and punctuation do not matter. N-grams missing from the sample get a floor
+probability of 0.01 / total, so one unseen n-gram does not rule a candidate
+out.
+*/
+
+use std::sync::OnceLock;
+
+const ENGLISH: &str = include_str!("../data/english.txt");
+
+pub struct NgramModel {
+    n: usize,
+    // Indexed by the n-gram read as a base 26 number
+    log_probs: Vec<f64>,
+    floor: f64,
+}
+
+// The letters of text as 0..26, ignoring case and everything else
+pub fn letters(text: &str) -> Vec<usize> {
+    text.chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| (c.to_ascii_lowercase() as u8 - b'a') as usize)
+        .collect()
+}
+
+fn index(gram: &[usize]) -> usize {
+    gram.iter().fold(0, |acc, &letter| acc * 26 + letter)
+}
+
+impl NgramModel {
+    // Count the n-grams of a training text
+    pub fn from_text(text: &str, n: usize) -> NgramModel {
+        let mut counts = vec![0u32; 26usize.pow(n as u32)];
+        for gram in letters(text).windows(n) {
+            counts[index(gram)] += 1;
+        }
+        let total = counts.iter().sum::<u32>().max(1) as f64;
+        let floor = (0.01 / total).log10();
+        let log_probs = counts
+            .iter()
+            .map(|&count| {
+                if count == 0 {
+                    floor
+                } else {
+                    (count as f64 / total).log10()
+                }
+            })
+            .collect();
+        NgramModel {
+            n,
+            log_probs,
+            floor,
+        }
+    }
+
+    pub fn english_bigrams() -> &'static NgramModel {
+        static MODEL: OnceLock<NgramModel> = OnceLock::new();
+        MODEL.get_or_init(|| NgramModel::from_text(ENGLISH, 2))
+    }
+
+    pub fn english_trigrams() -> &'static NgramModel {
+        static MODEL: OnceLock<NgramModel> = OnceLock::new();
+        MODEL.get_or_init(|| NgramModel::from_text(ENGLISH, 3))
+    }
+
+    pub fn english_quadgrams() -> &'static NgramModel {
+        static MODEL: OnceLock<NgramModel> = OnceLock::new();
+        MODEL.get_or_init(|| NgramModel::from_text(ENGLISH, 4))
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    // Log10 probability of an n-gram given as letters 0..26
+    pub fn log_prob(&self, gram: &[usize]) -> f64 {
+        self.log_probs[index(gram)]
+    }
+
+    // Sum of the log probabilities of every n-gram, higher is more English
+    pub fn score(&self, text: &str) -> f64 {
+        self.score_letters(&letters(text))
+    }
+
+    // The same for text that is already split into letters 0..26
+    pub fn score_letters(&self, letters: &[usize]) -> f64 {
+        if letters.len() < self.n {
+            return self.floor;
+        }
+        letters
+            .windows(self.n)
+            .map(|gram| self.log_prob(gram))
+            .sum()
+    }
+}
@@ -0,0 +1,95 @@
+/*
+How guess_shift decides which candidate plaintext looks most like English.
+
+chi-squared  distance between the letter frequencies of the candidate and
+             the language model, works well once there are a few dozen
+             letters
+bigram       log probability of every pair of adjacent English letters
+quadgram     log probability of every run of four English letters, the
+             most reliable on short English messages
+
+The n-gram tables are English only, so bigram and quadgram scoring ignore
+the language model; only chi-squared uses its letter frequencies.
+
+Every score is higher for better candidates, so chi-squared is negated.
+Text without letters scores negative infinity with every method; text with
+fewer letters than an n-gram gets the n-gram floor.
+*/
+
+use crate::language::LanguageModel;
+use crate::ngram::{letters, NgramModel};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scoring {
+    #[default]
+    ChiSquared,
+    Bigram,
+    Quadgram,
+}
+
+impl Scoring {
+    pub const ALL: [Scoring; 3] = [Scoring::ChiSquared, Scoring::Bigram, Scoring::Quadgram];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scoring::ChiSquared => "chi-squared",
+            Scoring::Bigram => "bigram",
+            Scoring::Quadgram => "quadgram",
+        }
+    }
+
+    // Higher is more like the language, negative infinity for text without letters
+    pub fn score(&self, text: &str, model: &LanguageModel) -> f64 {
+        let letters = letters(text);
+        if letters.is_empty() {
+            return f64::NEG_INFINITY;
+        }
+        match self {
+            Scoring::ChiSquared => model
+                .chi_squared(text)
+                .map_or(f64::NEG_INFINITY, |chi| -chi),
+            Scoring::Bigram => NgramModel::english_bigrams().score_letters(&letters),
+            Scoring::Quadgram => NgramModel::english_quadgrams().score_letters(&letters),
+        }
+    }
+}
+
+impl fmt::Display for Scoring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scoring::ALL
+            .into_iter()
+            .find(|scoring| scoring.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown scoring '{}', expected chi-squared, bigram or quadgram",
+                    s
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_without_letters() {
+        let english = LanguageModel::english();
+        for scoring in Scoring::ALL {
+            assert_eq!(scoring.score("42 !", &english), f64::NEG_INFINITY);
+            assert!(scoring.score("ab", &english).is_finite());
+        }
+    }
+}
@@ -0,0 +1,301 @@
+/*
+Frequency analysis report of a ciphertext.
+
+Only letters are counted, ignoring case. The report has:
+
+ * every letter by how often it occurs, with a bar chart of its share next
+   to the share the language model expects
+ * the index of coincidence: the chance that two letters picked at random
+   are the same, which any monoalphabetic cipher keeps at the level of the
+   language, while polyalphabetic ciphers flatten it towards 1 / 26
+ * the Shannon entropy of the letters in bits, which rises towards
+   log2(26) = 4.70 as the letters get more uniform
+ * the most common bigrams and trigrams of the letters, spaces removed
+ * a hint of the cipher family that these statistics point to
+
+The hint is a heuristic. When the index of coincidence is closer to the
+language than to random text, the letters were substituted one for one: if
+one shift of the alphabet makes the frequencies fit much better than any
+other, it is a shift cipher, or a transposition when that shift is 0, and
+otherwise a general substitution. Below that it is polyalphabetic, such as
+Vigenere. Messages of fewer than MIN_LETTERS letters get no hint.
+*/
+
+use crate::language::LanguageModel;
+use crate::ngram::letters;
+use crate::vigenere::{index_of_coincidence, RANDOM_IOC};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+// Fewer letters than this say too little about the cipher
+const MIN_LETTERS: usize = 40;
+
+// Number of bigrams and trigrams in the report
+const TOP_NGRAMS: usize = 10;
+
+// Widest bar of the chart, for the largest share
+const BAR_WIDTH: usize = 30;
+
+// The best shift fits clearly when its chi-squared is below this share of the runner-up
+const CLEAR_SHIFT: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherFamily {
+    TooShort,
+    Transposition,
+    Shift,
+    Substitution,
+    Polyalphabetic,
+}
+
+impl CipherFamily {
+    pub fn description(&self) -> &'static str {
+        match self {
+            CipherFamily::TooShort => "too few letters to tell",
+            CipherFamily::Transposition => {
+                "plaintext or a transposition, the letter frequencies already fit"
+            }
+            CipherFamily::Shift => "a shift cipher such as Caesar, try --guess",
+            CipherFamily::Substitution => "a monoalphabetic substitution, try --substitution",
+            CipherFamily::Polyalphabetic => {
+                "a polyalphabetic cipher such as Vigenere, try --vigenere"
+            }
+        }
+    }
+}
+
+impl fmt::Display for CipherFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetterCount {
+    pub letter: char,
+    pub count: usize,
+    // Percent of the letters of the message
+    pub percent: f64,
+    // Percent the language model expects
+    pub expected: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsReport {
+    pub language: String,
+    pub letters: usize,
+    // All 26 letters, most common first, ties in alphabetical order
+    pub counts: Vec<LetterCount>,
+    pub index_of_coincidence: f64,
+    pub expected_ioc: f64,
+    // Bits per letter
+    pub entropy: f64,
+    pub expected_entropy: f64,
+    // Most common first, ties in alphabetical order
+    pub bigrams: Vec<(String, usize)>,
+    pub trigrams: Vec<(String, usize)>,
+    pub family: CipherFamily,
+}
+
+// Shannon entropy in bits of a distribution given as weights
+fn entropy(weights: &[f64]) -> f64 {
+    let total: f64 = weights.iter().sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    -weights
+        .iter()
+        .filter(|&&w| w > 0.0)
+        .map(|w| w / total * (w / total).log2())
+        .sum::<f64>()
+}
+
+fn top_ngrams(letters: &[usize], n: usize) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for gram in letters.windows(n) {
+        let gram: String = gram.iter().map(|&l| (b'a' + l as u8) as char).collect();
+        *counts.entry(gram).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP_NGRAMS);
+    counts
+}
+
+fn cipher_family(text: &str, letters: usize, ioc: f64, model: &LanguageModel) -> CipherFamily {
+    if letters < MIN_LETTERS {
+        return CipherFamily::TooShort;
+    }
+    let closeness = (ioc - RANDOM_IOC) / (model.index_of_coincidence() - RANDOM_IOC);
+    if closeness < 0.5 {
+        return CipherFamily::Polyalphabetic;
+    }
+
+    let mut fits: Vec<(f64, u8)> = (0..26)
+        .map(|shift| {
+            let chi = model.chi_squared(&crate::decrypt(text, shift));
+            (chi.unwrap_or(f64::INFINITY), shift)
+        })
+        .collect();
+    fits.sort_by(|a, b| a.0.total_cmp(&b.0));
+    match fits[0] {
+        (chi, _) if chi >= CLEAR_SHIFT * fits[1].0 => CipherFamily::Substitution,
+        (_, 0) => CipherFamily::Transposition,
+        _ => CipherFamily::Shift,
+    }
+}
+
+// Analyse the letters of text against the frequencies of a language
+pub fn analyse(text: &str, model: &LanguageModel) -> StatsReport {
+    let letters = letters(text);
+    let mut counts = [0usize; 26];
+    for &letter in &letters {
+        counts[letter] += 1;
+    }
+    let total = letters.len();
+
+    let mut letter_counts: Vec<LetterCount> = (0..26)
+        .map(|i| LetterCount {
+            letter: (b'a' + i as u8) as char,
+            count: counts[i],
+            percent: if total == 0 {
+                0.0
+            } else {
+                counts[i] as f64 * 100.0 / total as f64
+            },
+            expected: model.frequencies()[i],
+        })
+        .collect();
+    // Stable, so equal counts stay in alphabetical order
+    letter_counts.sort_by_key(|c| Reverse(c.count));
+
+    let ioc = index_of_coincidence(text);
+    let weights: Vec<f64> = counts.iter().map(|&c| c as f64).collect();
+    StatsReport {
+        language: model.name().to_string(),
+        letters: total,
+        counts: letter_counts,
+        index_of_coincidence: ioc,
+        expected_ioc: model.index_of_coincidence(),
+        entropy: entropy(&weights),
+        expected_entropy: entropy(model.frequencies()),
+        bigrams: top_ngrams(&letters, 2),
+        trigrams: top_ngrams(&letters, 3),
+        family: cipher_family(text, total, ioc, model),
+    }
+}
+
+fn bar(percent: f64, scale: f64) -> String {
+    "#".repeat((percent * scale).round() as usize)
+}
+
+fn ngram_list(ngrams: &[(String, usize)]) -> String {
+    ngrams
+        .iter()
+        .map(|(gram, count)| format!("{} {}", gram, count))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Letters: {}", self.letters)?;
+        writeln!(
+            f,
+            "Index of coincidence: {:.4} ({} {:.4}, random {:.4})",
+            self.index_of_coincidence, self.language, self.expected_ioc, RANDOM_IOC
+        )?;
+        writeln!(
+            f,
+            "Entropy: {:.2} bits per letter ({} {:.2}, random {:.2})",
+            self.entropy,
+            self.language,
+            self.expected_entropy,
+            26f64.log2()
+        )?;
+        writeln!(f)?;
+
+        let largest = self
+            .counts
+            .iter()
+            .map(|c| c.percent.max(c.expected))
+            .fold(0.0, f64::max);
+        let scale = if largest > 0.0 {
+            BAR_WIDTH as f64 / largest
+        } else {
+            0.0
+        };
+        writeln!(
+            f,
+            "Letter  Count  {:<w$}  {}",
+            "Message",
+            self.language,
+            w = BAR_WIDTH + 8
+        )?;
+        for c in &self.counts {
+            writeln!(
+                f,
+                "{:<6}  {:>5}  {:>6.2}% {:<w$}  {:>6.2}% {}",
+                c.letter,
+                c.count,
+                c.percent,
+                bar(c.percent, scale),
+                c.expected,
+                bar(c.expected, scale),
+                w = BAR_WIDTH
+            )?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Top bigrams: {}", ngram_list(&self.bigrams))?;
+        writeln!(f, "Top trigrams: {}", ngram_list(&self.trigrams))?;
+        write!(f, "Likely cipher: {}", self.family)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use caesar_cipher::Cipher;
+    use caesar_cipher::Vigenere;
+
+    // Walden, which is not part of the n-gram corpus in data/english.txt
+    const PLAIN: &str = "I went to the woods because I wished to live deliberately, to \
+        front only the essential facts of life, and see if I could not learn what it had \
+        to teach, and not, when I came to die, discover that I had not lived. I did not \
+        wish to live what was not life, living is so dear; nor did I wish to practise \
+        resignation, unless it was quite necessary.";
+
+    #[test]
+    fn test_counts_letters_only() {
+        let report = analyse("Aa b!", &LanguageModel::english());
+        let counts: Vec<(char, usize)> = report.counts[..3]
+            .iter()
+            .map(|c| (c.letter, c.count))
+            .collect();
+        assert_eq!(counts, vec![('a', 2), ('b', 1), ('c', 0)]);
+        assert_eq!(report.letters, 3);
+        assert_eq!(
+            report.bigrams,
+            vec![("aa".to_string(), 1), ("ab".to_string(), 1)]
+        );
+        assert!((report.entropy - 0.9183).abs() < 1e-4);
+        assert_eq!(report.family, CipherFamily::TooShort);
+    }
+
+    #[test]
+    fn test_cipher_family() {
+        let english = LanguageModel::english();
+        let family = |text: &str| analyse(text, &english).family;
+        assert_eq!(family(PLAIN), CipherFamily::Transposition);
+        assert_eq!(
+            family(&caesar_cipher::encrypt(PLAIN, 9)),
+            CipherFamily::Shift
+        );
+        let substituted = crate::substitution::apply_key(PLAIN, "qwertyuiopasdfghjklzxcvbnm");
+        assert_eq!(family(&substituted), CipherFamily::Substitution);
+        let vigenere = Vigenere::new("lemon").unwrap().encrypt(PLAIN);
+        assert_eq!(family(&vigenere), CipherFamily::Polyalphabetic);
+    }
+}
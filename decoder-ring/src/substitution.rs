@@ -0,0 +1,213 @@
+/*
+General monoalphabetic substitution solver.
+
+Every cipher letter stands for one plaintext letter, so there are 26! keys
+and no way to try them all. The solver hill-climbs instead:
+
+1. Start from the frequency key: the most common cipher letter maps to the
+   most common English letter (e), the next one to t, and so on.
+2. Try swapping the plaintext letters of every pair of cipher letters and
+   keep a swap whenever it raises the fitness of the plaintext: the sum of
+   its bigram, trigram and quadgram log probabilities. The quadgrams decide
+   between good keys, the shorter n-grams guide the climb while most
+   quadgrams are still unseen.
+3. Stop when no swap helps. The climb can get stuck on a local maximum, so
+   it restarts from shuffled keys and keeps the best result of all climbs.
+
+The search stops after the configured number of restarts or when the time
+budget runs out, whichever comes first, and returns the best key so far.
+A few hundred letters are usually enough to recover the message, except
+for rare letters such as j, q and z that may occur too seldom to place.
+*/
+
+use crate::ngram::{letters, NgramModel};
+use caesar_cipher::crack::ENGLISH_FREQUENCIES;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolverConfig {
+    // Climbs after the first one, which starts from the frequency key
+    pub restarts: usize,
+    pub time_budget: Duration,
+    // Seed of the shuffled keys, the same seed gives the same result
+    pub seed: u64,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            restarts: 100,
+            time_budget: Duration::from_secs(10),
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubstitutionSolution {
+    // The plaintext letter of each cipher letter a-z
+    pub key: String,
+    pub plaintext: String,
+    // N-gram log probability of the plaintext, higher is better
+    pub fitness: f64,
+    // Number of climbs that ran within the time budget
+    pub climbs: usize,
+}
+
+type Key = [usize; 26];
+
+// Map the cipher letters by how common they are onto English letters by how common they are
+fn frequency_key(letters: &[usize]) -> Key {
+    let mut counts = [0usize; 26];
+    for &letter in letters {
+        counts[letter] += 1;
+    }
+    let mut cipher: Vec<usize> = (0..26).collect();
+    cipher.sort_by_key(|&c| (std::cmp::Reverse(counts[c]), c));
+    let mut english: Vec<usize> = (0..26).collect();
+    english.sort_by(|&a, &b| ENGLISH_FREQUENCIES[b].total_cmp(&ENGLISH_FREQUENCIES[a]));
+
+    let mut key = [0; 26];
+    for (c, e) in cipher.into_iter().zip(english) {
+        key[c] = e;
+    }
+    key
+}
+
+fn fitness(letters: &[usize], key: &Key, mapped: &mut [usize], models: &[&NgramModel]) -> f64 {
+    for (m, &letter) in mapped.iter_mut().zip(letters) {
+        *m = key[letter];
+    }
+    models.iter().map(|model| model.score_letters(mapped)).sum()
+}
+
+// Whether the deadline has passed, None for a budget too large to ever run out
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+// Swap pairs of the key while that improves the fitness, or until the deadline
+fn climb(
+    letters: &[usize],
+    key: &mut Key,
+    models: &[&NgramModel],
+    deadline: Option<Instant>,
+) -> f64 {
+    let mut mapped = vec![0; letters.len()];
+    let mut best = fitness(letters, key, &mut mapped, models);
+    loop {
+        let mut improved = false;
+        for i in 0..26 {
+            for j in i + 1..26 {
+                key.swap(i, j);
+                let score = fitness(letters, key, &mut mapped, models);
+                if score > best {
+                    best = score;
+                    improved = true;
+                } else {
+                    key.swap(i, j);
+                }
+            }
+            if expired(deadline) {
+                return best;
+            }
+        }
+        if !improved {
+            return best;
+        }
+    }
+}
+
+// Apply a key to text, keeping case and everything that is not a letter
+pub fn apply_key(text: &str, key: &str) -> String {
+    let key: Vec<char> = key.chars().collect();
+    text.chars()
+        .map(|c| {
+            if !c.is_ascii_alphabetic() {
+                return c;
+            }
+            let plain = key[(c.to_ascii_lowercase() as u8 - b'a') as usize];
+            if c.is_ascii_uppercase() {
+                plain.to_ascii_uppercase()
+            } else {
+                plain
+            }
+        })
+        .collect()
+}
+
+// The best key and plaintext found, None when text has no letters
+pub fn solve_substitution(text: &str, config: &SolverConfig) -> Option<SubstitutionSolution> {
+    let letters = letters(text);
+    if letters.is_empty() {
+        return None;
+    }
+    let models = [
+        NgramModel::english_bigrams(),
+        NgramModel::english_trigrams(),
+        NgramModel::english_quadgrams(),
+    ];
+    let deadline = Instant::now().checked_add(config.time_budget);
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let mut key = frequency_key(&letters);
+    let mut best_fitness = climb(&letters, &mut key, &models, deadline);
+    let mut best_key = key;
+    let mut climbs = 1;
+
+    while climbs <= config.restarts && !expired(deadline) {
+        key.shuffle(&mut rng);
+        let fitness = climb(&letters, &mut key, &models, deadline);
+        if fitness > best_fitness {
+            best_fitness = fitness;
+            best_key = key;
+        }
+        climbs += 1;
+    }
+
+    let key: String = best_key.iter().map(|&p| (b'a' + p as u8) as char).collect();
+    Some(SubstitutionSolution {
+        plaintext: apply_key(text, &key),
+        key,
+        fitness: best_fitness,
+        climbs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &str = "The quarterly report was late again because the export from the \
+        billing system stopped halfway through the night. Nobody noticed until the morning, \
+        when the dashboards showed half of the usual revenue and the finance team started \
+        asking questions. We traced the problem to a full disk on the database server, \
+        cleaned up the old backups and ran the whole job once more before lunch.";
+
+    #[test]
+    fn test_solve_substitution() {
+        let key = "qwertyuiopasdfghjklzxcvbnm";
+        let cipher = apply_key(PLAIN, key);
+        // Limited by restarts only, so the number of climbs does not depend on the machine
+        let config = SolverConfig {
+            restarts: 5,
+            time_budget: Duration::MAX,
+            ..Default::default()
+        };
+        let solution = solve_substitution(&cipher, &config).unwrap();
+
+        // Letters that occur once or never, like j and z here, cannot always be pinned down
+        let correct = solution
+            .plaintext
+            .chars()
+            .zip(PLAIN.chars())
+            .filter(|(a, b)| a == b)
+            .count();
+        assert!(correct as f64 / PLAIN.len() as f64 > 0.99);
+        assert_eq!(solution.climbs, 6);
+        assert_eq!(solve_substitution("42", &config), None);
+    }
+}
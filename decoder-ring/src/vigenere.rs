@@ -0,0 +1,215 @@
+/*
+Automatic Vigenere cracking.
+
+1. Estimate the key length. For every candidate length the letters are split
+   into columns, one per key letter. Each column of the right length is a
+   plain Caesar cipher, so its index of coincidence is close to English
+   (0.067) instead of random text (0.038). Kasiski examination adds the share
+   of distances between repeated trigrams that the length divides, beyond
+   the 1 / length it would divide by chance.
+2. Crack each column as a Caesar cipher with the chi-squared frequency
+   scorer, which gives one key letter per column.
+3. Decrypt the whole message with the recovered key. The best few key
+   lengths are all tried, and the plaintext that scores best on quadgrams
+   wins, since a key like "pipeline" makes length 4 look nearly as good as 8.
+
+The confidence is between 0 and 1. It multiplies how English the columns
+look (their index of coincidence between random and English) by how clearly
+the best shift of each column beats the runner-up.
+*/
+
+use crate::language::LanguageModel;
+use crate::ngram::letters;
+use crate::scoring::Scoring;
+use caesar_cipher::crack::{chi_squared, ENGLISH_FREQUENCIES};
+use caesar_cipher::{Caesar, Cipher, Vigenere};
+use std::collections::HashMap;
+
+pub const ENGLISH_IOC: f64 = 0.0667;
+pub const RANDOM_IOC: f64 = 1.0 / 26.0;
+
+// How many of the best estimated key lengths are tried
+const CANDIDATE_LENGTHS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VigenereGuess {
+    pub key: String,
+    pub plaintext: String,
+    pub confidence: f64,
+}
+
+// Chance that two letters picked at random from the text are the same
+pub fn index_of_coincidence(text: &str) -> f64 {
+    ioc(&letters(text))
+}
+
+fn ioc(letters: &[usize]) -> f64 {
+    let n = letters.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let mut counts = [0usize; 26];
+    for &letter in letters {
+        counts[letter] += 1;
+    }
+    let pairs: usize = counts.iter().map(|&c| c * c.saturating_sub(1)).sum();
+    pairs as f64 / (n * (n - 1)) as f64
+}
+
+fn columns(letters: &[usize], key_length: usize) -> Vec<Vec<usize>> {
+    let mut columns = vec![Vec::new(); key_length];
+    for (i, &letter) in letters.iter().enumerate() {
+        columns[i % key_length].push(letter);
+    }
+    columns
+}
+
+fn mean_column_ioc(letters: &[usize], key_length: usize) -> f64 {
+    let columns = columns(letters, key_length);
+    columns.iter().map(|column| ioc(column)).sum::<f64>() / key_length as f64
+}
+
+// For every key length up to max, the share of repeated trigram distances it divides
+fn kasiski(letters: &[usize], max_key_length: usize) -> Vec<f64> {
+    let mut last_seen: HashMap<&[usize], usize> = HashMap::new();
+    let mut distances = Vec::new();
+    for (i, trigram) in letters.windows(3).enumerate() {
+        if let Some(previous) = last_seen.insert(trigram, i) {
+            distances.push(i - previous);
+        }
+    }
+    (0..=max_key_length)
+        .map(|length| {
+            if length == 0 || distances.is_empty() {
+                return 0.0;
+            }
+            let divided = distances
+                .iter()
+                .filter(|&&d| d.is_multiple_of(length))
+                .count();
+            divided as f64 / distances.len() as f64
+        })
+        .collect()
+}
+
+// Candidate key lengths with their score, best first
+pub fn estimate_key_lengths(text: &str, max_key_length: usize) -> Vec<(usize, f64)> {
+    let letters = letters(text);
+    // Every column needs a few letters for its statistics to mean anything
+    let max_key_length = max_key_length.min(letters.len() / 4).max(1);
+    let kasiski = kasiski(&letters, max_key_length);
+
+    let mut lengths: Vec<(usize, f64)> = (1..=max_key_length)
+        .map(|length| {
+            let ioc = mean_column_ioc(&letters, length);
+            let closeness = (ioc - RANDOM_IOC) / (ENGLISH_IOC - RANDOM_IOC);
+            // Any length divides 1 / length of the distances by chance
+            (length, closeness + kasiski[length] - 1.0 / length as f64)
+        })
+        .collect();
+    lengths.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    lengths
+}
+
+// The shift of a column and how clearly it beats the second best, 0..1
+fn crack_column(column: &[usize]) -> (usize, f64) {
+    let text: String = column.iter().map(|&l| (b'a' + l as u8) as char).collect();
+    let mut scores: Vec<(f64, usize)> = (0..26)
+        .map(|shift| {
+            let plain = Caesar::new(shift).decrypt(&text);
+            (
+                chi_squared(&plain, &ENGLISH_FREQUENCIES).unwrap_or(f64::INFINITY),
+                shift,
+            )
+        })
+        .collect();
+    scores.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let margin = 1.0 - scores[0].0 / scores[1].0;
+    (scores[0].1, margin)
+}
+
+// The shortest key that repeats to the given one, "lemonlemon" becomes "lemon"
+fn shortest_period(key: &[usize]) -> &[usize] {
+    (1..key.len())
+        .find(|&period| {
+            key.len().is_multiple_of(period) && key.chunks(period).all(|c| c == &key[..period])
+        })
+        .map_or(key, |period| &key[..period])
+}
+
+// Key letters for a key length and how clearly each column's shift won
+fn crack_columns(letters: &[usize], key_length: usize) -> (Vec<usize>, Vec<f64>) {
+    columns(letters, key_length)
+        .iter()
+        .map(|column| crack_column(column))
+        .unzip()
+}
+
+// Recover the key and plaintext of a Vigenere ciphertext, None without letters
+pub fn crack_vigenere(text: &str, max_key_length: usize) -> Option<VigenereGuess> {
+    let letters = letters(text);
+    if letters.is_empty() {
+        return None;
+    }
+
+    // A key with repeated letters can make a divisor of its length look as
+    // good, so the best few lengths are decrypted and judged by quadgrams
+    let mut best: Option<(f64, VigenereGuess)> = None;
+    for (key_length, _) in estimate_key_lengths(text, max_key_length)
+        .into_iter()
+        .take(CANDIDATE_LENGTHS)
+    {
+        let (shifts, margins) = crack_columns(&letters, key_length);
+        let shifts = shortest_period(&shifts);
+        let key: String = shifts.iter().map(|&s| (b'a' + s as u8) as char).collect();
+        let plaintext = Vigenere::new(&key).ok()?.decrypt(text);
+        let fitness = Scoring::Quadgram.score(&plaintext, &LanguageModel::english());
+        if best
+            .as_ref()
+            .is_some_and(|(best_fitness, _)| *best_fitness >= fitness)
+        {
+            continue;
+        }
+
+        let ioc = mean_column_ioc(&letters, shifts.len());
+        let closeness = ((ioc - RANDOM_IOC) / (ENGLISH_IOC - RANDOM_IOC)).clamp(0.0, 1.0);
+        let margin = margins.iter().sum::<f64>() / margins.len() as f64;
+        let guess = VigenereGuess {
+            key,
+            plaintext,
+            confidence: closeness * margin.clamp(0.0, 1.0),
+        };
+        best = Some((fitness, guess));
+    }
+    best.map(|(_, guess)| guess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Walden, which is not part of the n-gram corpus in data/english.txt
+    const PLAIN: &str = "I went to the woods because I wished to live deliberately, to \
+        front only the essential facts of life, and see if I could not learn what it had \
+        to teach, and not, when I came to die, discover that I had not lived. I did not \
+        wish to live what was not life, living is so dear; nor did I wish to practise \
+        resignation, unless it was quite necessary.";
+
+    #[test]
+    fn test_crack_vigenere() {
+        for key in ["lemon", "data", "pipeline"] {
+            let cipher = Vigenere::new(key).unwrap().encrypt(PLAIN);
+            let guess = crack_vigenere(&cipher, 20).unwrap();
+            assert_eq!(guess.key, key);
+            assert_eq!(guess.plaintext, PLAIN);
+            assert!(guess.confidence > 0.3, "{}", guess.confidence);
+        }
+        assert_eq!(crack_vigenere("1234", 20), None);
+    }
+
+    #[test]
+    fn test_index_of_coincidence() {
+        assert!((index_of_coincidence(PLAIN) - ENGLISH_IOC).abs() < 0.01);
+        assert_eq!(index_of_coincidence("a"), 0.0);
+    }
+}
@@ -0,0 +1,437 @@
+/*
+ * A homophonic cipher key: every lowercase letter of the English alphabet
+ * stands for a set of cipher symbols, its homophones, and encryption picks
+ * one of them at random for each letter of the plaintext.
+ *
+ * The symbols are the two-digit codes 00 to 99 by default. Every symbol
+ * belongs to exactly one letter, so decryption is exact, and common letters
+ * get more symbols than rare ones: each letter has at least one, and the
+ * rest are shared out by English letter frequency, so 'e' gets 12 codes
+ * and 'z' one. This flattens the symbol frequencies of the ciphertext,
+ * which is what makes frequency analysis harder than on a simple
+ * substitution cipher.
+ *
+ * The same seed always generates the same key, and a key can be saved to
+ * and loaded from JSON, here the key of seed 42 in short:
+ *
+ * {
+ *   "homophones": {
+ *     "a": ["02", "35", "36", "44", "57", "61", "88", "96"],
+ *     ...
+ *   }
+ * }
+ *
+ * Encryption lowercases the plaintext and drops everything that is not a
+ * letter, so the ciphertext is a run of symbols without separators.
+ *
+ * encrypt_preserving keeps the rest of the plaintext instead: spaces,
+ * digits and punctuation pass through, and so does case. With the letters
+ * a-z as symbols an uppercase letter gets an uppercase symbol; there are
+ * only 26 of them, one per letter, so that key is a simple substitution.
+ * Wider symbol alphabets such as the two-digit codes have room for more
+ * homophones but no case, so an uppercase letter's symbol is marked with a
+ * leading '^'. Any character of the plaintext that could be mistaken for a
+ * symbol or a marker, such as a digit among codes, is escaped with '\':
+ *
+ * Plaintext:  Meet me at 10:30!
+ * Ciphertext: ^22082141 2219 8800 \1\0:\3\0!
+ */
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Letter frequencies in English, in percent, for a to z
+pub const ENGLISH_FREQUENCIES: [f64; 26] = [
+    8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966, 0.153, 0.772, 4.025, 2.406,
+    6.749, 7.507, 1.929, 0.095, 5.987, 6.327, 9.056, 2.758, 0.978, 2.360, 0.150, 1.974, 0.074,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedKey")]
+pub struct HomophonicKey {
+    // The symbols of each letter a-z, no symbol belongs to two letters
+    homophones: BTreeMap<char, Vec<String>>,
+}
+
+// A key as it is read, before validate has checked it
+#[derive(Deserialize)]
+struct UncheckedKey {
+    homophones: BTreeMap<char, Vec<String>>,
+}
+
+impl TryFrom<UncheckedKey> for HomophonicKey {
+    type Error = String;
+
+    fn try_from(unchecked: UncheckedKey) -> Result<Self, Self::Error> {
+        let key = HomophonicKey {
+            homophones: unchecked.homophones,
+        };
+        key.validate()?;
+        Ok(key)
+    }
+}
+
+// Marks the symbol of an uppercase letter when the symbols have no case
+pub const UPPERCASE_MARK: char = '^';
+
+// Marks a character of the plaintext that is not a symbol
+pub const ESCAPE: char = '\\';
+
+// The letters a to z, one symbol per letter
+pub fn letter_symbols() -> Vec<String> {
+    ('a'..='z').map(String::from).collect()
+}
+
+// The codes 00 to 99
+pub fn two_digit_codes() -> Vec<String> {
+    (0..100).map(|code| format!("{:02}", code)).collect()
+}
+
+// How many of count symbols each letter gets: at least one, the rest by frequency
+fn homophone_counts(count: usize) -> [usize; 26] {
+    let quotas = ENGLISH_FREQUENCIES.map(|f| f / 100.0 * count as f64);
+    let mut counts = quotas.map(|q| (q.floor() as usize).max(1));
+    loop {
+        let total: usize = counts.iter().sum();
+        if total == count {
+            return counts;
+        }
+        // Give a symbol to the letter furthest below its quota, or take one
+        // from the letter furthest above it that can spare one
+        let shortfall = |i: usize| quotas[i] - counts[i] as f64;
+        if total < count {
+            let i = (0..26)
+                .max_by(|&a, &b| shortfall(a).total_cmp(&shortfall(b)))
+                .unwrap();
+            counts[i] += 1;
+        } else {
+            let i = (0..26)
+                .filter(|&i| counts[i] > 1)
+                .min_by(|&a, &b| shortfall(a).total_cmp(&shortfall(b)))
+                .unwrap();
+            counts[i] -= 1;
+        }
+    }
+}
+
+impl HomophonicKey {
+    // A random key over the two-digit codes
+    pub fn generate(seed: u64) -> HomophonicKey {
+        HomophonicKey::with_symbols(two_digit_codes(), seed).unwrap()
+    }
+
+    // A random key over any symbols, at least 26, distinct and of the same length
+    pub fn with_symbols(symbols: Vec<String>, seed: u64) -> Result<HomophonicKey, String> {
+        if symbols.len() < 26 {
+            return Err(format!(
+                "{} symbols are too few, every letter needs one",
+                symbols.len()
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut symbols = symbols;
+        symbols.shuffle(&mut rng);
+
+        let mut homophones = BTreeMap::new();
+        let mut symbols = symbols.into_iter();
+        for (letter, count) in ('a'..='z').zip(homophone_counts(symbols.len())) {
+            let mut set: Vec<String> = symbols.by_ref().take(count).collect();
+            set.sort();
+            homophones.insert(letter, set);
+        }
+        let key = HomophonicKey { homophones };
+        key.validate()?;
+        Ok(key)
+    }
+
+    // Every letter has symbols, all the same length, and no symbol is used twice
+    pub fn validate(&self) -> Result<(), String> {
+        let mut owners: HashMap<&str, char> = HashMap::new();
+        let mut width = None;
+        for letter in 'a'..='z' {
+            let set = match self.homophones.get(&letter) {
+                Some(set) if !set.is_empty() => set,
+                _ => return Err(format!("no homophones for '{}'", letter)),
+            };
+            for symbol in set {
+                let len = symbol.chars().count();
+                if symbol
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == UPPERCASE_MARK || c == ESCAPE)
+                {
+                    return Err(format!(
+                        "symbol '{}' of '{}' has whitespace, '{}' or '{}'",
+                        symbol, letter, UPPERCASE_MARK, ESCAPE
+                    ));
+                }
+                if len == 0 || *width.get_or_insert(len) != len {
+                    return Err(format!(
+                        "symbol '{}' of '{}' is not {} characters long",
+                        symbol,
+                        letter,
+                        width.unwrap_or(1)
+                    ));
+                }
+                if let Some(owner) = owners.insert(symbol, letter) {
+                    return Err(format!(
+                        "symbol '{}' belongs to both '{}' and '{}'",
+                        symbol, owner, letter
+                    ));
+                }
+            }
+        }
+        if let Some(&letter) = self.homophones.keys().find(|c| !c.is_ascii_lowercase()) {
+            return Err(format!("'{}' is not a lowercase letter", letter));
+        }
+        Ok(())
+    }
+
+    // The symbols of a letter, None for anything but a-z
+    pub fn homophones(&self, letter: char) -> Option<&[String]> {
+        self.homophones.get(&letter).map(Vec::as_slice)
+    }
+
+    // Number of characters in each symbol
+    pub fn symbol_width(&self) -> usize {
+        self.homophones[&'a'][0].chars().count()
+    }
+
+    // Replace every letter with one of its symbols, chosen with rng
+    pub fn encrypt<R: Rng + ?Sized>(&self, plaintext: &str, rng: &mut R) -> String {
+        plaintext
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| {
+                let set = &self.homophones[&c.to_ascii_lowercase()];
+                set[rng.gen_range(0..set.len())].as_str()
+            })
+            .collect()
+    }
+
+    // The letter of every symbol
+    fn owners(&self) -> HashMap<&str, char> {
+        self.homophones
+            .iter()
+            .flat_map(|(&letter, set)| set.iter().map(move |symbol| (symbol.as_str(), letter)))
+            .collect()
+    }
+
+    // Every character that occurs in a symbol
+    fn symbol_chars(&self) -> HashSet<char> {
+        self.homophones
+            .values()
+            .flatten()
+            .flat_map(|s| s.chars())
+            .collect()
+    }
+
+    // Whether the symbols are lowercase letters, which can carry the case of the plaintext
+    fn is_cased(&self) -> bool {
+        self.homophones
+            .values()
+            .flatten()
+            .all(|symbol| symbol.chars().all(|c| c.is_ascii_lowercase()))
+    }
+
+    // The lowercase plaintext of a ciphertext made by encrypt, whitespace is ignored
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String, String> {
+        let owners = self.owners();
+        let symbols: Vec<char> = ciphertext.chars().filter(|c| !c.is_whitespace()).collect();
+        let width = self.symbol_width();
+        if !symbols.len().is_multiple_of(width) {
+            return Err(format!(
+                "ciphertext of {} characters is not made of {} character symbols",
+                symbols.len(),
+                width
+            ));
+        }
+        symbols
+            .chunks(width)
+            .map(|chunk| {
+                let symbol: String = chunk.iter().collect();
+                owners
+                    .get(symbol.as_str())
+                    .copied()
+                    .ok_or_else(|| format!("unknown symbol '{}'", symbol))
+            })
+            .collect()
+    }
+
+    // Like encrypt, but keep case and everything that is not a letter
+    pub fn encrypt_preserving<R: Rng + ?Sized>(&self, plaintext: &str, rng: &mut R) -> String {
+        let cased = self.is_cased();
+        let symbol_chars = self.symbol_chars();
+        let mut ciphertext = String::new();
+        for c in plaintext.chars() {
+            if c.is_ascii_alphabetic() {
+                let set = &self.homophones[&c.to_ascii_lowercase()];
+                let symbol = &set[rng.gen_range(0..set.len())];
+                match (c.is_ascii_uppercase(), cased) {
+                    (false, _) => ciphertext.push_str(symbol),
+                    (true, true) => ciphertext.push_str(&symbol.to_ascii_uppercase()),
+                    (true, false) => {
+                        ciphertext.push(UPPERCASE_MARK);
+                        ciphertext.push_str(symbol);
+                    }
+                }
+            } else {
+                if c == ESCAPE || c == UPPERCASE_MARK || symbol_chars.contains(&c) {
+                    ciphertext.push(ESCAPE);
+                }
+                ciphertext.push(c);
+            }
+        }
+        ciphertext
+    }
+
+    // The plaintext of a ciphertext made by encrypt_preserving
+    pub fn decrypt_preserving(&self, ciphertext: &str) -> Result<String, String> {
+        let owners = self.owners();
+        let cased = self.is_cased();
+        let width = self.symbol_width();
+        let symbol_chars = self.symbol_chars();
+        // Only the symbols of a cased key are uppercased to carry the case of a letter
+        let fold = |c: char| if cased { c.to_ascii_lowercase() } else { c };
+        let mut plaintext = String::new();
+        let mut chars = ciphertext.chars().peekable();
+        while let Some(c) = chars.next() {
+            let uppercase = match c {
+                ESCAPE => {
+                    let escaped = chars
+                        .next()
+                        .ok_or_else(|| format!("'{}' at the end of the ciphertext", ESCAPE))?;
+                    plaintext.push(escaped);
+                    continue;
+                }
+                UPPERCASE_MARK if !cased => {
+                    if chars.peek().is_none() {
+                        return Err(format!("'{}' at the end of the ciphertext", UPPERCASE_MARK));
+                    }
+                    true
+                }
+                _ if symbol_chars.contains(&fold(c)) => false,
+                _ => {
+                    plaintext.push(c);
+                    continue;
+                }
+            };
+            // The symbol starts at c, unless c was the uppercase mark
+            let mut symbol: String = if uppercase { String::new() } else { c.into() };
+            symbol.extend(chars.by_ref().take(width - symbol.chars().count()));
+            let uppercase = uppercase || (cased && symbol.chars().all(|c| c.is_ascii_uppercase()));
+            let folded: String = symbol.chars().map(fold).collect();
+            let letter = owners
+                .get(folded.as_str())
+                .ok_or_else(|| format!("unknown symbol '{}'", symbol))?;
+            plaintext.push(if uppercase {
+                letter.to_ascii_uppercase()
+            } else {
+                *letter
+            });
+        }
+        Ok(plaintext)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    // Parse a key saved by to_json, deserializing validates it
+    pub fn from_json(json: &str) -> Result<HomophonicKey, String> {
+        serde_json::from_str(json).map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json()? + "\n")
+    }
+
+    pub fn load(path: &Path) -> io::Result<HomophonicKey> {
+        let json = fs::read_to_string(path)?;
+        HomophonicKey::from_json(&json).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_homophones_are_disjoint_and_sized_by_frequency() {
+        let key = HomophonicKey::generate(7);
+        assert_eq!(key.validate(), Ok(()));
+        let total: usize = ('a'..='z').map(|c| key.homophones(c).unwrap().len()).sum();
+        assert_eq!(total, 100);
+        assert_eq!(key.homophones('e').unwrap().len(), 12);
+        assert_eq!(key.homophones('z').unwrap().len(), 1);
+        assert_eq!(key, HomophonicKey::generate(7));
+        assert_ne!(key, HomophonicKey::generate(8));
+        assert!(HomophonicKey::with_symbols(vec!["x".to_string(); 25], 0).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = HomophonicKey::generate(1);
+        let mut rng = StdRng::seed_from_u64(2);
+        let ciphertext = key.encrypt("The quick brown fox, jumps over the lazy dog!", &mut rng);
+        assert_eq!(ciphertext.len(), 35 * 2);
+        assert_eq!(
+            key.decrypt(&ciphertext).unwrap(),
+            "thequickbrownfoxjumpsoverthelazydog"
+        );
+        assert!(key.decrypt("123").is_err());
+    }
+
+    #[test]
+    fn test_preserving_round_trip() {
+        let plaintext = "Meet me at 10:30! ^Caret\\ and café";
+        let mut rng = StdRng::seed_from_u64(4);
+        for key in [
+            HomophonicKey::generate(5),
+            HomophonicKey::with_symbols(letter_symbols(), 5).unwrap(),
+        ] {
+            let ciphertext = key.encrypt_preserving(plaintext, &mut rng);
+            assert_eq!(key.decrypt_preserving(&ciphertext).unwrap(), plaintext);
+        }
+
+        let letters = HomophonicKey::with_symbols(letter_symbols(), 5).unwrap();
+        let ciphertext = letters.encrypt_preserving("Hi, 42", &mut rng);
+        assert_eq!(ciphertext.len(), 6);
+        assert!(ciphertext.ends_with(", 42"));
+        assert!(ciphertext.starts_with(|c: char| c.is_ascii_uppercase()));
+
+        // Uppercase symbols of a key without case are kept as they are
+        let capitals = ('A'..='Z').map(String::from).collect();
+        let capitals = HomophonicKey::with_symbols(capitals, 5).unwrap();
+        let ciphertext = capitals.encrypt_preserving("No, Bob is here", &mut rng);
+        assert_eq!(
+            capitals.decrypt_preserving(&ciphertext).unwrap(),
+            "No, Bob is here"
+        );
+
+        let codes = HomophonicKey::generate(5);
+        assert!(codes.decrypt_preserving("1").is_err());
+        assert!(codes.decrypt_preserving("^").is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let key = HomophonicKey::generate(3);
+        assert_eq!(HomophonicKey::from_json(&key.to_json().unwrap()), Ok(key));
+
+        let clash = r#"{"homophones": {"a": ["1"], "b": ["1"]}}"#;
+        assert!(HomophonicKey::from_json(clash).is_err());
+        let empty: Result<HomophonicKey, _> = serde_json::from_str(r#"{"homophones": {}}"#);
+        assert!(empty.is_err());
+    }
+}
@@ -1,103 +1,243 @@
 /*
- * Generates a list of random homophones for each lowercase letter in the
- * English alphabet. Maps each character in the plaintext to one of its 
- * random homophones to create the cipher text. Prints the plaintext, 
- * cipher text, and homophonic mapping. Returns the cipher text and 
- * homophonic mapping.
- * 
+ * Encrypts and decrypts messages with a homophonic cipher, see lib.rs for
+ * how the keys are made. Without a subcommand it generates a key, encrypts
+ * a pangram with it, decrypts it again and prints the key:
+ *
+ * cargo run -- --seed 42
  *
  * Here is an example:
  * Plaintext: the quick brown fox jumps over the lazy dog
- * Ciphertext: acrsalgzuwxsgpeqqrjrnekrwvnnwdgfuqn
- * Mapping: {
- *     't': ['q', 'a', 'v'], 'y': ['f', 's'], 'q': ['s', 'u'], 
- *     'l': ['w', 'z', 'o'], 's': ['i', 'w', 'n'], 'b': ['u', 'f'], 
- *     'h': ['n', 'n', 'c'], 'k': ['z', 'r'], 'j': ['s', 'w', 'q'], 
- *     'x': ['g', 'g', 'q'], 'i': ['l', 'k'], 'g': ['n', 'g'], 
- *     'm': ['s', 'j', 'w'], 'p': ['k', 'r'], 'a': ['d', 'm', 'w'], 
- *     'r': ['w', 'o', 'o'], 'o': ['q', 'x', 'e'], 'e': ['n', 'r'], 
- *     'f': ['i', 'p', 'e'], 'c': ['g', 'z'], 'u': ['a', 'd', 'r'], 
- *     'v': ['h', 'f', 'k'], 'd': ['s', 'r', 'u'], 'n': ['d', 'g', 'l'], 
- *     'w': ['s', 'c'], 'z': ['g', 'b']
- * } 
- * The mapping {'t': ['q', 'a', 'v'], ...} is a part of the homophonic 
- * cipher mapping from plaintext characters to their cipher characters.
+ * Ciphertext: 3784083456013072391823605079129873902268628993141837854217961352102354
+ * Decrypted: thequickbrownfoxjumpsoverthelazydog
+ * Mapping:
+ * a: 02 35 36 44 57 61 88 96
+ * b: 39
+ * c: 07 30 86
+ * d: 10 63 66 75
+ * e: 08 14 15 19 20 21 25 26 28 42 43 58
+ * f: 74 79
+ * g: 54 64
+ * h: 05 71 84 85 95 99
+ * i: 01 04 53 67 78 80 91
+ * j: 73
+ * k: 72
+ * l: 17 27 33 76
+ * m: 22 45
+ * n: 38 50 51 55 81 94
+ * o: 09 12 23 29 32 89 97
+ * p: 06 68
+ * q: 34
+ * r: 18 46 47 48 59 87
+ * s: 31 40 62 70 82 92
+ * t: 00 11 16 37 41 49 65 69 77
+ * u: 03 56 90
+ * v: 93
+ * w: 60 83
+ * x: 98
+ * y: 24 52
+ * z: 13
  *
-
- * In this specific example, the plaintext character 't' can be represented
- * in the ciphertext by either 'q', 'a', or 'v'. This introduces ambiguity 
- * into the encryption, which makes the homophonic cipher harder to break 
- * compared to simple substitution ciphers.
+ * The mapping is the homophonic cipher key from plaintext letters to their
+ * cipher symbols. In this example, the plaintext letter 't' can be written
+ * as any one of its codes. Every code belongs to one letter only, so the
+ * ciphertext is read back two digits at a time, each code giving its
+ * letter, and decryption is exact.
+ *
+ * Common letters get more codes, so every code turns up about as often as
+ * any other and the ciphertext does not give away which codes stand for
+ * 'e' or 't'. This makes the homophonic cipher harder to break than a
+ * simple substitution cipher, but it is still not secure for serious
+ * cryptographic uses: pairs and runs of letters still show through, and a
+ * known-plaintext attack or the context of the message can give it away.
+ *
+ * To keep a key and use it again, save it as JSON:
+ *
+ * cargo run -- keygen --seed 42 --output key.json
+ * cargo run -- encrypt --seed 42 --key key.json --message "Meet me at noon"
+ * cargo run -- decrypt --key key.json --message "220821412219880051092950"
  *
- * The homophonic cipher is more secure than a simple substitution cipher, 
- * it is still not secure for serious cryptographic uses.
+ * Without --seed the key, and the choice of homophones, is random.
  *
- * Given the ciphertext and the mapping, you can reverse-engineer the 
- * plaintext. Let's start with the first three characters of the 
- * ciphertext: 'a', 'c', and 'r'.
+ * The ciphertext only has the letters, lowercased. --preserve keeps case,
+ * spaces, digits and punctuation, escaping any that look like a code:
  *
- * 'a': Looking at the mapping, you can see that 'a' can be a cipher for 
- * 'u' or 't', as 'u' and 't' have 'a' in their list of homophones. So 
- * the possible plaintext letters for 'a' are 'u' and 't'.
+ * cargo run -- encrypt --preserve --seed 42 --key key.json --message "Meet me at 10:30!"
  *
- * 'c': Looking at the mapping again, 'c' can be a cipher for 'h' or 'w' 
- * since 'h' and 'w' have 'c' in their list of homophones. So the 
- * possible plaintext letters for 'c' are 'h' and 'w'.
+ * ^22082141 2219 8800 \1\0:\3\0!
  *
- * 'r': 'r' can be a cipher for 'e', 'o', or 't' since 'e', 'o', and 't' 
- * have 'r' in their list of homophones. So the possible plaintext 
- * letters for 'r' are 'e', 'o', and 't'.
+ * Keys over the letters a to z instead of the codes keep the look of the
+ * text, but with 26 symbols every letter has just one homophone:
  *
- * So the first three characters of the plaintext could be any combination
- * of the possible plaintext letters for 'a', 'c', and 'r'. For example, 
- * it could be 'u', 'h', 'e', or 't', 'w', 'o', etc.
+ * cargo run -- keygen --symbols letters --seed 1 --output letters.json
+ * cargo run -- encrypt --preserve --key letters.json --message "Meet me at 10:30, Bob."
  *
- * Remember, homophonic ciphers are designed to provide many possible 
- * plaintexts for a single ciphertext, which makes it much harder to crack
- * the code without having more information. One possible approach to 
- * decode the message is using a frequency analysis or a known-plaintext 
- * attack if you have a part of the original message. Another way is to 
- * use the context of the message if it's known.
+ * Hbbd hb xd 10:30, Zcz.
  */
 
-use rand::Rng;
-use std::collections::HashMap;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use homophonic_cipher::{letter_symbols, two_digit_codes, HomophonicKey};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
 
-fn homophonic_cipher(plaintext: &str) -> (String, HashMap<char, Vec<char>>) {
-    let mut rng = rand::thread_rng();
-    let alphabet: Vec<char> = ('a'..='z').collect();
-    let mut ciphertext = String::new();
-    let mut mapping: HashMap<char, Vec<char>> = HashMap::new();
+const PANGRAM: &str = "the quick brown fox jumps over the lazy dog";
+
+/// CLI tool to encrypt and decrypt messages with a homophonic cipher
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Seed of the random key and homophones, random when omitted
+    #[arg(short, long, global = true)]
+    seed: Option<u64>,
+
+    /// Symbols of the demo key: the codes 00 to 99 (default), or the letters a to z
+    #[arg(long, value_enum)]
+    symbols: Option<Symbols>,
+
+    /// Keep case, spaces, digits and punctuation of the demo pangram
+    #[arg(short, long)]
+    preserve: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Symbols {
+    Codes,
+    Letters,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Generate a key and print it as JSON
+    Keygen {
+        /// File to save the key to instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Symbols of the key: the codes 00 to 99, or the letters a to z
+        #[arg(long, value_enum, default_value = "codes")]
+        symbols: Symbols,
+    },
+    /// Encrypt the message with a saved key
+    Encrypt {
+        /// JSON file of the key
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// The message to encrypt
+        #[arg(short, long)]
+        message: String,
+
+        /// Keep case, spaces, digits and punctuation instead of only the letters
+        #[arg(short, long)]
+        preserve: bool,
+    },
+    /// Decrypt the message with a saved key
+    Decrypt {
+        /// JSON file of the key
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// The message to decrypt
+        #[arg(short, long)]
+        message: String,
+
+        /// Read a ciphertext made with encrypt --preserve
+        #[arg(short, long)]
+        preserve: bool,
+    },
+}
 
-    for c in &alphabet {
-        let homophones: Vec<char> = (0..rng.gen_range(2..4))
-            .map(|_| rng.gen_range('a'..='z'))
-            .collect();
-        mapping.insert(*c, homophones);
+fn print_mapping(key: &HomophonicKey) {
+    for letter in 'a'..='z' {
+        let homophones = key.homophones(letter).unwrap_or_default();
+        println!("{}: {}", letter, homophones.join(" "));
     }
+}
 
-    for c in plaintext.chars() {
-        if let Some(c) = c.to_lowercase().next() {
-            if let Some(homophones) = mapping.get(&c) {
-                if let Some(&homophone) = homophones.get(rng.gen_range(0..homophones.len())) {
-                    ciphertext.push(homophone);
-                } else {
-                    eprintln!("Error: No homophones for character {}", c);
-                }
-            }
-        } else {
-            ciphertext.push(c);
-        }
+fn new_key(symbols: Symbols, seed: u64) -> Result<HomophonicKey, String> {
+    match symbols {
+        Symbols::Codes => HomophonicKey::with_symbols(two_digit_codes(), seed),
+        Symbols::Letters => HomophonicKey::with_symbols(letter_symbols(), seed),
+    }
+}
+
+fn encrypt(key: &HomophonicKey, plaintext: &str, preserve: bool, rng: &mut StdRng) -> String {
+    if preserve {
+        key.encrypt_preserving(plaintext, rng)
+    } else {
+        key.encrypt(plaintext, rng)
+    }
+}
+
+fn decrypt(key: &HomophonicKey, ciphertext: &str, preserve: bool) -> Result<String, String> {
+    if preserve {
+        key.decrypt_preserving(ciphertext)
+    } else {
+        key.decrypt(ciphertext)
     }
+}
 
-    println!("Plaintext: {}", plaintext);
-    println!("Ciphertext: {}", ciphertext);
-    println!("Mapping: {:?}", mapping);
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let seed = cli.seed.unwrap_or_else(rand::random);
+    // Homophones are picked with a different stream than the key is made with
+    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(1));
 
-    (ciphertext, mapping)
+    match cli.command {
+        None => {
+            let key = new_key(cli.symbols.unwrap_or(Symbols::Codes), seed)?;
+            let ciphertext = encrypt(&key, PANGRAM, cli.preserve, &mut rng);
+            println!("Plaintext: {}", PANGRAM);
+            println!("Ciphertext: {}", ciphertext);
+            println!("Decrypted: {}", decrypt(&key, &ciphertext, cli.preserve)?);
+            println!("Mapping:");
+            print_mapping(&key);
+        }
+        Some(Commands::Keygen { output, symbols }) => {
+            let key = new_key(symbols, seed)?;
+            match output {
+                Some(path) => key.save(&path)?,
+                None => println!("{}", key.to_json()?),
+            }
+        }
+        Some(Commands::Encrypt {
+            key,
+            message,
+            preserve,
+        }) => {
+            let key = HomophonicKey::load(&key)?;
+            println!("{}", encrypt(&key, &message, preserve, &mut rng));
+        }
+        Some(Commands::Decrypt {
+            key,
+            message,
+            preserve,
+        }) => {
+            let key = HomophonicKey::load(&key)?;
+            println!("{}", decrypt(&key, &message, preserve)?);
+        }
+    }
+    Ok(())
 }
 
 fn main() {
-    let plaintext = "the quick brown fox jumps over the lazy dog";
-    homophonic_cipher(plaintext);
+    let cli = Cli::parse();
+    // The demo flags are not global, so they would be ignored before a subcommand
+    if cli.command.is_some() && (cli.symbols.is_some() || cli.preserve) {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--symbols and --preserve go after the subcommand",
+            )
+            .exit();
+    }
+    if let Err(err) = run(cli) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,53 @@
+/*
+Compares analyze_duplicates on one thread against all available cores.
+
+cargo bench
+
+The report is identical for every thread count; only the time changes.
+*/
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+use sha3_dupe_detector::{analyze_duplicates_with, with_hasher, Algorithm};
+
+fn records() -> Vec<String> {
+    (0..200_000)
+        .map(|i| {
+            format!(
+                "{},the old man looked at him with his sun burned confident loving eyes",
+                i % 50_000
+            )
+        })
+        .collect()
+}
+
+fn bench_parallel_hashing(c: &mut Criterion) {
+    let records = records();
+    let records: Vec<&str> = records.iter().map(String::as_str).collect();
+    let all_cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut threads = vec![1];
+    if all_cores > 1 {
+        threads.push(all_cores);
+    }
+
+    for algorithm in [Algorithm::Sha3_256, Algorithm::Xxh3] {
+        let mut group = c.benchmark_group(format!("analyze_duplicates/{}", algorithm));
+        group.throughput(Throughput::Elements(records.len() as u64));
+        group.sample_size(10);
+        for &n in &threads {
+            let pool = ThreadPoolBuilder::new().num_threads(n).build().unwrap();
+            group.bench_with_input(BenchmarkId::new("threads", n), &records, |b, records| {
+                b.iter(|| {
+                    pool.install(
+                        || with_hasher!(algorithm, h => analyze_duplicates_with(records, &h)),
+                    )
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_parallel_hashing);
+criterion_main!(benches);
@@ -0,0 +1,413 @@
+/*
+Column-keyed dedup of CSV records.
+
+Only some columns define the identity of a row, so rows are hashed on their
+key columns alone. Key values are normalised first (trimmed, whitespace
+collapsed, lowercased) and joined with the ASCII unit separator, so
+"a,b" + "c" and "a" + "b,c" never collide. Normalisers given in the options
+run on every key value after that, and each group lists the ones that made
+different keys collide.
+
+Rows are numbered from 1, starting with the first row after the header.
+analyze_csv reads the input once and keeps only digests and row numbers in
+memory; write_deduplicated reads it a second time and writes the rows that
+were chosen to survive. write_deduplicated_file refuses to write over its
+own input, since creating the output would empty the file before the
+second read.
+
+With fuzzy matching the report also lists clusters of keys that are similar
+but not equal, by the row where each key was first seen. They are only
+reported; every key still keeps one row of its own in the output.
+*/
+
+use crate::fuzzy::{find_near_duplicates, NearDuplicateCluster};
+use crate::hasher::DupeHasher;
+use crate::normalise::{self, explain_collision_by, Normaliser};
+use crate::report::{DuplicateGroup, DuplicateReport};
+use crate::MatchOptions;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// Which row of a duplicate group is kept in the deduplicated output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Keep {
+    #[default]
+    First,
+    Last,
+    // The row with the most non-empty fields, the first one on ties
+    MostComplete,
+}
+
+impl fmt::Display for Keep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Keep::First => "first",
+            Keep::Last => "last",
+            Keep::MostComplete => "most-complete",
+        })
+    }
+}
+
+impl FromStr for Keep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Keep::First),
+            "last" => Ok(Keep::Last),
+            "most-complete" => Ok(Keep::MostComplete),
+            _ => Err(format!(
+                "unknown keep policy '{}', expected first, last or most-complete",
+                s
+            )),
+        }
+    }
+}
+
+pub struct CsvDedup {
+    pub report: DuplicateReport,
+    // Row numbers that survive deduplication, in input order
+    pub keep_rows: Vec<usize>,
+}
+
+struct KeyEntry {
+    rows: Vec<usize>,
+    key: String,
+    // Distinct keys before the normalisers, values joined with the unit separator
+    forms: HashSet<String>,
+    keep: usize,
+    keep_filled: usize,
+}
+
+// Trim, collapse inner whitespace and lowercase a key value
+fn normalise(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Positions of the key columns in the header
+fn key_indices(headers: &csv::StringRecord, key: &[&str]) -> io::Result<Vec<usize>> {
+    key.iter()
+        .map(|column| {
+            headers.iter().position(|h| h == *column).ok_or_else(|| {
+                let available: Vec<&str> = headers.iter().collect();
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "no column '{}', available columns: {}",
+                        column,
+                        available.join(", ")
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+// Group rows by the digest of their normalised key columns
+pub fn analyze_csv<R: Read, H: DupeHasher>(
+    reader: R,
+    key: &[&str],
+    keep: Keep,
+    hasher: &H,
+) -> io::Result<CsvDedup> {
+    analyze_csv_matching(reader, key, keep, hasher, &MatchOptions::default())
+}
+
+// Run the normalisers over every value of a joined key
+fn normalise_key(key: &str, steps: &[Normaliser]) -> String {
+    key.split('\u{1f}')
+        .map(|value| normalise::normalise(value, steps))
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+// Same as analyze_csv, normalising keys first and clustering similar keys when the
+// options ask for it
+pub fn analyze_csv_matching<R: Read, H: DupeHasher>(
+    reader: R,
+    key: &[&str],
+    keep: Keep,
+    hasher: &H,
+    options: &MatchOptions,
+) -> io::Result<CsvDedup> {
+    let mut rdr = csv::Reader::from_reader(reader);
+    let indices = key_indices(rdr.headers()?, key)?;
+
+    let mut entries: HashMap<H::Digest, KeyEntry> = HashMap::new();
+    let mut total_records = 0;
+    let mut record = csv::StringRecord::new();
+    while rdr.read_record(&mut record)? {
+        total_records += 1;
+        let row = total_records;
+
+        let values: Vec<String> = indices.iter().map(|&i| normalise(&record[i])).collect();
+        let form = values.join("\u{1f}");
+        let digest = hasher.digest(normalise_key(&form, &options.normalise).as_bytes());
+        let filled = record
+            .iter()
+            .filter(|field| !field.trim().is_empty())
+            .count();
+
+        let entry = entries.entry(digest).or_insert_with(|| KeyEntry {
+            rows: Vec::new(),
+            key: indices
+                .iter()
+                .map(|&i| record[i].to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            forms: HashSet::new(),
+            keep: row,
+            keep_filled: filled,
+        });
+        entry.rows.push(row);
+        if !options.normalise.is_empty() {
+            entry.forms.insert(form);
+        }
+        let replace = match keep {
+            Keep::First => false,
+            Keep::Last => true,
+            Keep::MostComplete => filled > entry.keep_filled,
+        };
+        if replace {
+            entry.keep = row;
+            entry.keep_filled = filled;
+        }
+    }
+
+    let mut report = DuplicateReport {
+        algorithm: hasher.name().to_string(),
+        total_records,
+        unique_records: entries.len(),
+        ..Default::default()
+    };
+    let mut keep_rows = Vec::with_capacity(entries.len());
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    for (digest, entry) in entries {
+        keep_rows.push(entry.keep);
+        if options.fuzzy.is_some() {
+            keys.push((entry.rows[0], entry.key.clone()));
+        }
+        let count = entry.rows.len();
+        if count > 1 {
+            report.duplicate_groups += 1;
+            report.duplicate_records += count - 1;
+            report.groups.push(DuplicateGroup {
+                hash: hex::encode(digest),
+                count,
+                first_seen: entry.rows[0],
+                positions: entry.rows,
+                record: entry.key,
+                normalisations: explain(&entry.forms, &options.normalise),
+            });
+        }
+    }
+    report.groups.sort_by_key(|group| group.first_seen);
+    keep_rows.sort_unstable();
+
+    if let Some(config) = &options.fuzzy {
+        keys.sort_unstable();
+        let records: Vec<&str> = keys.iter().map(|(_, key)| key.as_str()).collect();
+        report.near_duplicates = find_near_duplicates(&records, config)
+            .into_iter()
+            .map(|cluster| NearDuplicateCluster {
+                positions: cluster.positions.iter().map(|&i| keys[i].0).collect(),
+                ..cluster
+            })
+            .collect();
+    }
+
+    Ok(CsvDedup { report, keep_rows })
+}
+
+// Names of the normalisers that made the distinct forms of a key collide
+fn explain(forms: &HashSet<String>, steps: &[Normaliser]) -> Vec<String> {
+    if forms.len() < 2 {
+        return Vec::new();
+    }
+    let forms: Vec<&str> = forms.iter().map(String::as_str).collect();
+    explain_collision_by(&forms, steps, normalise_key)
+        .iter()
+        .map(|step| step.name().to_string())
+        .collect()
+}
+
+// Copy the header and the kept rows of the same input to writer
+pub fn write_deduplicated<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    keep_rows: &[usize],
+) -> io::Result<()> {
+    let keep: HashSet<usize> = keep_rows.iter().copied().collect();
+    let mut rdr = csv::Reader::from_reader(reader);
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(rdr.headers()?)?;
+
+    let mut record = csv::ByteRecord::new();
+    let mut row = 0;
+    while rdr.read_byte_record(&mut record)? {
+        row += 1;
+        if keep.contains(&row) {
+            wtr.write_byte_record(&record)?;
+        }
+    }
+    wtr.flush()
+}
+
+// Same as write_deduplicated, from the input file to a different output file
+pub fn write_deduplicated_file(input: &Path, output: &Path, keep_rows: &[usize]) -> io::Result<()> {
+    if let (Ok(a), Ok(b)) = (input.canonicalize(), output.canonicalize()) {
+        if a == b {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is both the input and the output", input.display()),
+            ));
+        }
+    }
+    let reader = BufReader::new(File::open(input)?);
+    let writer = BufWriter::new(File::create(output)?);
+    write_deduplicated(reader, writer, keep_rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::Sha3_256Hasher;
+
+    const CSV: &str = "\
+id,name,city,email
+1,Santiago,Havana,
+2,Manolin,Havana,manolin@example.com
+3, santiago ,HAVANA,santiago@example.com
+4,Santiago,Havana,
+";
+
+    #[test]
+    fn test_analyze_csv_groups_by_key() {
+        let dedup = analyze_csv(
+            CSV.as_bytes(),
+            &["name", "city"],
+            Keep::First,
+            &Sha3_256Hasher,
+        )
+        .unwrap();
+
+        assert_eq!(dedup.report.total_records, 4);
+        assert_eq!(dedup.report.duplicate_groups, 1);
+        assert_eq!(dedup.report.groups[0].positions, vec![1, 3, 4]);
+        assert_eq!(dedup.report.groups[0].record, "Santiago,Havana");
+        assert_eq!(dedup.keep_rows, vec![1, 2]);
+
+        let last = analyze_csv(
+            CSV.as_bytes(),
+            &["name", "city"],
+            Keep::Last,
+            &Sha3_256Hasher,
+        )
+        .unwrap();
+        assert_eq!(last.keep_rows, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_write_deduplicated_keeps_most_complete_row() {
+        let dedup = analyze_csv(
+            CSV.as_bytes(),
+            &["name", "city"],
+            Keep::MostComplete,
+            &Sha3_256Hasher,
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        write_deduplicated(CSV.as_bytes(), &mut out, &dedup.keep_rows).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,name,city,email\n\
+             2,Manolin,Havana,manolin@example.com\n\
+             3, santiago ,HAVANA,santiago@example.com\n"
+        );
+    }
+
+    #[test]
+    fn test_write_deduplicated_file_refuses_its_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("people.csv");
+        std::fs::write(&path, CSV).unwrap();
+        let same = dir.path().join(".").join("people.csv");
+
+        let err = write_deduplicated_file(&path, &same, &[1, 2]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), CSV);
+
+        let output = dir.path().join("deduped.csv");
+        write_deduplicated_file(&path, &output, &[2]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "id,name,city,email\n2,Manolin,Havana,manolin@example.com\n"
+        );
+    }
+
+    #[test]
+    fn test_analyze_csv_normalised_keys() {
+        let csv = "name,city\nSantiago!,Havana\nSantiago,Havana.\nManolin,Havana\n";
+        let options = MatchOptions {
+            normalise: vec![Normaliser::Punctuation],
+            ..Default::default()
+        };
+        let dedup = analyze_csv_matching(
+            csv.as_bytes(),
+            &["name", "city"],
+            Keep::First,
+            &Sha3_256Hasher,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(dedup.report.duplicate_groups, 1);
+        assert_eq!(dedup.report.groups[0].positions, vec![1, 2]);
+        assert_eq!(dedup.report.groups[0].normalisations, vec!["punctuation"]);
+        assert_eq!(dedup.keep_rows, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_analyze_csv_fuzzy_keys() {
+        let csv = "id,quote\n\
+            1,man can be destroyed but not defeated\n\
+            2,the old man was thin and gaunt\n\
+            3,a man can be destroyed but not defeated\n";
+        let options = MatchOptions {
+            fuzzy: Some(Default::default()),
+            ..Default::default()
+        };
+        let dedup = analyze_csv_matching(
+            csv.as_bytes(),
+            &["quote"],
+            Keep::First,
+            &Sha3_256Hasher,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(dedup.report.duplicate_groups, 0);
+        assert_eq!(dedup.report.near_duplicates.len(), 1);
+        assert_eq!(dedup.report.near_duplicates[0].positions, vec![1, 3]);
+        assert_eq!(dedup.keep_rows, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_analyze_csv_unknown_column() {
+        let err = analyze_csv(CSV.as_bytes(), &["zip"], Keep::First, &Sha3_256Hasher)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("available columns: id, name, city, email"));
+    }
+}
@@ -0,0 +1,296 @@
+/*
+Near-duplicate detection with MinHash.
+
+Exact SHA3 matching treats "man can be destroyed but not defeated" and
+"a man can be destroyed but not defeated" as different records. Here every
+record is split into overlapping character shingles and summarised by a
+MinHash signature; the fraction of equal signature slots estimates the
+Jaccard similarity of the two shingle sets.
+
+Comparing every pair of signatures is quadratic, so candidate pairs are
+found with locality sensitive hashing: the signature is cut into bands and
+records sharing any band land in the same bucket. Candidates whose estimated
+similarity reaches the threshold are joined into clusters.
+*/
+
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinHashConfig {
+    // Number of hash functions in a signature
+    pub num_hashes: usize,
+    // Characters per shingle
+    pub shingle_size: usize,
+    // Minimum estimated Jaccard similarity for two records to be clustered
+    pub threshold: f64,
+}
+
+impl Default for MinHashConfig {
+    fn default() -> Self {
+        MinHashConfig {
+            num_hashes: 128,
+            shingle_size: 4,
+            threshold: 0.8,
+        }
+    }
+}
+
+// Distinct records that are similar but not identical
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NearDuplicateCluster {
+    // Position where each distinct record was first seen
+    pub positions: Vec<usize>,
+    pub records: Vec<String>,
+    // Lowest estimated similarity of the pairs that formed the cluster
+    pub min_similarity: f64,
+}
+
+// 64-bit FNV-1a, stable across runs and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// SplitMix64 finaliser, used to derive the independent hash functions
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// Hashes of the overlapping character shingles of a record
+fn shingles(text: &str, size: usize) -> HashSet<u64> {
+    let chars: Vec<char> = text.chars().collect();
+    let size = size.max(1);
+    if chars.len() <= size {
+        let shingle: String = chars.iter().collect();
+        return HashSet::from([fnv1a(shingle.as_bytes())]);
+    }
+    chars
+        .windows(size)
+        .map(|w| fnv1a(w.iter().collect::<String>().as_bytes()))
+        .collect()
+}
+
+// MinHash signature of a record
+pub fn signature(text: &str, config: &MinHashConfig) -> Vec<u64> {
+    let shingles = shingles(text, config.shingle_size);
+    (0..config.num_hashes as u64)
+        .map(|i| {
+            let seed = mix(i);
+            shingles
+                .iter()
+                .map(|s| mix(s ^ seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+// Estimated Jaccard similarity of two signatures
+pub fn similarity(a: &[u64], b: &[u64]) -> f64 {
+    if a.is_empty() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
+// Pick the rows per band whose LSH threshold (1/b)^(1/r) is closest below the target
+fn rows_per_band(num_hashes: usize, threshold: f64) -> usize {
+    (1..=num_hashes)
+        .filter(|rows| {
+            let bands = (num_hashes / rows) as f64;
+            (1.0 / bands).powf(1.0 / *rows as f64) <= threshold
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+// Root of i in a union-find forest, halving the path on the way
+pub(crate) fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// Join the sets of a and b under the lower of their roots
+pub(crate) fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (root_a, root_b) = (find(parent, a), find(parent, b));
+    parent[root_a.max(root_b)] = root_a.min(root_b);
+}
+
+// Groups of signatures whose pairs reach the threshold, as sorted indices with
+// the lowest similarity of the pairs that formed each group, in order of first index
+pub fn cluster_signatures(
+    signatures: &[Vec<u64>],
+    config: &MinHashConfig,
+) -> Vec<(Vec<usize>, f64)> {
+    let rows = rows_per_band(config.num_hashes, config.threshold);
+    let mut candidates: HashSet<(usize, usize)> = HashSet::new();
+    for band in 0..config.num_hashes / rows {
+        let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+        for (i, sig) in signatures.iter().enumerate() {
+            buckets
+                .entry(&sig[band * rows..(band + 1) * rows])
+                .or_default()
+                .push(i);
+        }
+        for bucket in buckets.values() {
+            for (n, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[n + 1..] {
+                    candidates.insert((a, b));
+                }
+            }
+        }
+    }
+
+    let mut parent: Vec<usize> = (0..signatures.len()).collect();
+    let mut edges: Vec<(usize, f64)> = Vec::new();
+    for (a, b) in candidates {
+        let score = similarity(&signatures[a], &signatures[b]);
+        if score >= config.threshold {
+            edges.push((a, score));
+            union(&mut parent, a, b);
+        }
+    }
+
+    let mut clusters: HashMap<usize, (Vec<usize>, f64)> = HashMap::new();
+    for (a, score) in edges {
+        let root = find(&mut parent, a);
+        let cluster = clusters.entry(root).or_insert((Vec::new(), 1.0));
+        cluster.1 = cluster.1.min(score);
+    }
+    for i in 0..signatures.len() {
+        let root = find(&mut parent, i);
+        if let Some(cluster) = clusters.get_mut(&root) {
+            cluster.0.push(i);
+        }
+    }
+
+    let mut clusters: Vec<(Vec<usize>, f64)> = clusters.into_values().collect();
+    clusters.sort_by_key(|(members, _)| members[0]);
+    clusters
+}
+
+// Group distinct records into clusters of near-duplicates
+pub fn find_near_duplicates(records: &[&str], config: &MinHashConfig) -> Vec<NearDuplicateCluster> {
+    // Exact repeats are already covered by the SHA3 groups
+    let mut seen: HashSet<&str> = HashSet::new();
+    let distinct: Vec<(usize, &str)> = records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| seen.insert(record))
+        .map(|(position, record)| (position, *record))
+        .collect();
+
+    let signatures: Vec<Vec<u64>> = distinct
+        .par_iter()
+        .map(|(_, record)| signature(record, config))
+        .collect();
+
+    cluster_signatures(&signatures, config)
+        .into_iter()
+        .map(|(members, min_similarity)| NearDuplicateCluster {
+            positions: members.iter().map(|&i| distinct[i].0).collect(),
+            records: members.iter().map(|&i| distinct[i].1.to_string()).collect(),
+            min_similarity,
+        })
+        .collect()
+}
+
+// Signatures of the records kept so far, for checking a stream one record at a time.
+// Unlike find_near_duplicates this keeps every signature in memory, and a record is
+// only compared with the records that were kept, not with the ones it rejected.
+pub struct NearDuplicateIndex {
+    config: MinHashConfig,
+    rows: usize,
+    // One bucket map per band, from the band of a signature to the kept records
+    buckets: Vec<HashMap<Vec<u64>, Vec<usize>>>,
+    signatures: Vec<Vec<u64>>,
+}
+
+impl NearDuplicateIndex {
+    pub fn new(config: MinHashConfig) -> Self {
+        let rows = rows_per_band(config.num_hashes, config.threshold);
+        NearDuplicateIndex {
+            config,
+            rows,
+            buckets: vec![HashMap::new(); config.num_hashes / rows],
+            signatures: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    // Keep the record unless it is a near-duplicate of a kept one; true when it was kept
+    pub fn insert(&mut self, text: &str) -> bool {
+        let sig = signature(text, &self.config);
+        let rows = self.rows;
+        let similar = self.buckets.iter().enumerate().any(|(band, buckets)| {
+            buckets
+                .get(&sig[band * rows..(band + 1) * rows])
+                .is_some_and(|kept| {
+                    kept.iter()
+                        .any(|&i| similarity(&self.signatures[i], &sig) >= self.config.threshold)
+                })
+        });
+        if similar {
+            return false;
+        }
+        let id = self.signatures.len();
+        for (band, buckets) in self.buckets.iter_mut().enumerate() {
+            buckets
+                .entry(sig[band * rows..(band + 1) * rows].to_vec())
+                .or_default()
+                .push(id);
+        }
+        self.signatures.push(sig);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_near_duplicates() {
+        let records = [
+            "man can be destroyed but not defeated",
+            "but man is not made for defeat",
+            "a man can be destroyed but not defeated",
+            "man can be destroyed but not defeated",
+            "the old man was thin and gaunt",
+        ];
+        let clusters = find_near_duplicates(&records, &MinHashConfig::default());
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].positions, vec![0, 2]);
+        assert!(clusters[0].min_similarity >= 0.8);
+    }
+
+    #[test]
+    fn test_near_duplicate_index() {
+        let mut index = NearDuplicateIndex::new(MinHashConfig::default());
+        assert!(index.insert("man can be destroyed but not defeated"));
+        assert!(!index.insert("a man can be destroyed but not defeated"));
+        assert!(index.insert("the old man was thin and gaunt"));
+        assert_eq!(index.len(), 2);
+    }
+}
@@ -0,0 +1,368 @@
+/*
+Seedable generator of labelled synthetic duplicates.
+
+generate_random_phrases uses thread_rng and a fixed 1..=3 copies, so its runs
+cannot be reproduced. A Generator takes a seed, a corpus (the built-in
+phrases or one record per line of a file), a distribution for the number of
+copies of each record and a rate of near-duplicates. Near-duplicates are
+copies changed by one mutation: a typo, a casing change, extra whitespace or
+trailing punctuation. A mutation that leaves the text as it was, like casing
+text that is already uppercase, is tried again a few times; if it never
+changes the text the copy is labelled exact.
+
+Every generated record is labelled with the corpus line it came from, so
+evaluate can score a DuplicateReport with pairwise precision and recall: two
+records are a true pair when they share a source, and a predicted pair when
+the report puts them in the same exact group or near-duplicate cluster.
+*/
+
+use crate::fuzzy::{find, union};
+use crate::report::DuplicateReport;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+// Times a mutation is applied before a copy that it leaves unchanged stays exact
+const MUTATION_ATTEMPTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mutation {
+    Typo,
+    Casing,
+    Whitespace,
+    Punctuation,
+}
+
+impl Mutation {
+    pub const ALL: [Mutation; 4] = [
+        Mutation::Typo,
+        Mutation::Casing,
+        Mutation::Whitespace,
+        Mutation::Punctuation,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mutation::Typo => "typo",
+            Mutation::Casing => "casing",
+            Mutation::Whitespace => "whitespace",
+            Mutation::Punctuation => "punctuation",
+        }
+    }
+
+    pub fn apply<R: Rng>(&self, text: &str, rng: &mut R) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+        if chars.is_empty() {
+            return text.to_string();
+        }
+        match self {
+            // Substitute, drop or swap a single character
+            Mutation::Typo => {
+                let i = rng.gen_range(0..chars.len());
+                match rng.gen_range(0..3) {
+                    0 => chars[i] = rng.gen_range(b'a'..=b'z') as char,
+                    1 if chars.len() > 1 => {
+                        chars.remove(i);
+                    }
+                    _ if i + 1 < chars.len() => chars.swap(i, i + 1),
+                    _ => chars[i] = rng.gen_range(b'a'..=b'z') as char,
+                }
+            }
+            Mutation::Casing => {
+                if rng.gen_bool(0.5) {
+                    return text.to_uppercase();
+                }
+                chars[0] = chars[0].to_uppercase().next().unwrap_or(chars[0]);
+            }
+            Mutation::Whitespace => {
+                let i = rng.gen_range(0..=chars.len());
+                chars.insert(i, ' ');
+            }
+            Mutation::Punctuation => {
+                chars.push(*[',', '.', '!', '?'].choose(rng).unwrap());
+            }
+        }
+        chars.into_iter().collect()
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Mutation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Mutation::ALL
+            .into_iter()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown mutation '{}', expected typo, casing, whitespace or punctuation",
+                    s
+                )
+            })
+    }
+}
+
+// How many times each corpus record appears in the output
+#[derive(Debug, Clone, PartialEq)]
+pub enum Copies {
+    // Uniformly between min and max times, both inclusive
+    Uniform { min: usize, max: usize },
+    // weights[i] is the relative chance that a record appears i + 1 times
+    Weighted(Vec<f64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Label {
+    // The first copy of a corpus record
+    Original,
+    // An unchanged copy
+    Exact,
+    // A copy changed by a mutation
+    Near,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LabelledRecord {
+    pub text: String,
+    // Line of the corpus the record was generated from
+    pub source: usize,
+    pub label: Label,
+    pub mutation: Option<Mutation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generator {
+    pub seed: u64,
+    pub corpus: Vec<String>,
+    pub copies: Copies,
+    // Chance that a copy is a near-duplicate instead of an exact one
+    pub near_duplicate_rate: f64,
+    pub mutations: Vec<Mutation>,
+}
+
+impl Default for Generator {
+    // Same shape as generate_random_phrases, but reproducible
+    fn default() -> Self {
+        Generator {
+            seed: 0,
+            corpus: crate::PHRASES.iter().map(|p| p.to_string()).collect(),
+            copies: Copies::Uniform { min: 1, max: 3 },
+            near_duplicate_rate: 0.0,
+            mutations: Mutation::ALL.to_vec(),
+        }
+    }
+}
+
+impl Generator {
+    // Read the corpus from a file, one record per non-empty line
+    pub fn load_corpus(&mut self, path: &Path) -> io::Result<()> {
+        self.corpus = fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+        Ok(())
+    }
+
+    // Generate the shuffled, labelled records; the same seed gives the same records
+    pub fn generate(&self) -> Result<Vec<LabelledRecord>, String> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let weights = match &self.copies {
+            Copies::Uniform { min, max } if min > max || *max == 0 => {
+                return Err(format!("invalid copy range {}..={}", min, max))
+            }
+            Copies::Uniform { min, max } => (1..=*max)
+                .map(|n| if n >= *min { 1.0 } else { 0.0 })
+                .collect(),
+            Copies::Weighted(weights) => weights.clone(),
+        };
+        let copies = WeightedIndex::new(&weights).map_err(|err| err.to_string())?;
+        if !(0.0..=1.0).contains(&self.near_duplicate_rate) {
+            return Err(format!(
+                "near-duplicate rate {} is not between 0 and 1",
+                self.near_duplicate_rate
+            ));
+        }
+
+        let mut records = Vec::new();
+        for (source, text) in self.corpus.iter().enumerate() {
+            let n = copies.sample(&mut rng) + 1;
+            for copy in 0..n {
+                let mut record = LabelledRecord {
+                    text: text.clone(),
+                    source,
+                    label: if copy == 0 {
+                        Label::Original
+                    } else {
+                        Label::Exact
+                    },
+                    mutation: None,
+                };
+                if copy > 0 && !self.mutations.is_empty() && rng.gen_bool(self.near_duplicate_rate)
+                {
+                    let mutation = *self.mutations.choose(&mut rng).unwrap();
+                    let changed = (0..MUTATION_ATTEMPTS)
+                        .map(|_| mutation.apply(text, &mut rng))
+                        .find(|mutated| mutated != text);
+                    if let Some(mutated) = changed {
+                        record.text = mutated;
+                        record.label = Label::Near;
+                        record.mutation = Some(mutation);
+                    }
+                }
+                records.push(record);
+            }
+        }
+        records.shuffle(&mut rng);
+
+        Ok(records)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Evaluation {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+fn pairs(n: usize) -> usize {
+    n * n.saturating_sub(1) / 2
+}
+
+// Pairwise precision and recall of a report over labelled records
+pub fn evaluate(records: &[LabelledRecord], report: &DuplicateReport) -> Evaluation {
+    let mut parent: Vec<usize> = (0..records.len()).collect();
+    let predicted = report
+        .groups
+        .iter()
+        .map(|g| &g.positions)
+        .chain(report.near_duplicates.iter().map(|c| &c.positions));
+    for positions in predicted {
+        for pair in positions.windows(2) {
+            union(&mut parent, pair[0], pair[1]);
+        }
+    }
+
+    let mut predicted_sizes: HashMap<usize, usize> = HashMap::new();
+    let mut source_sizes: HashMap<usize, usize> = HashMap::new();
+    let mut both_sizes: HashMap<(usize, usize), usize> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        let root = find(&mut parent, i);
+        *predicted_sizes.entry(root).or_insert(0) += 1;
+        *source_sizes.entry(record.source).or_insert(0) += 1;
+        *both_sizes.entry((root, record.source)).or_insert(0) += 1;
+    }
+
+    let true_positives: usize = both_sizes.values().map(|&n| pairs(n)).sum();
+    let predicted_pairs: usize = predicted_sizes.values().map(|&n| pairs(n)).sum();
+    let actual_pairs: usize = source_sizes.values().map(|&n| pairs(n)).sum();
+
+    let ratio = |a: usize, b: usize| if b == 0 { 1.0 } else { a as f64 / b as f64 };
+    let precision = ratio(true_positives, predicted_pairs);
+    let recall = ratio(true_positives, actual_pairs);
+    let f1 = if precision + recall == 0.0 {
+        0.0
+    } else {
+        2.0 * precision * recall / (precision + recall)
+    };
+
+    Evaluation {
+        true_positives,
+        false_positives: predicted_pairs - true_positives,
+        false_negatives: actual_pairs - true_positives,
+        precision,
+        recall,
+        f1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze_duplicates;
+
+    #[test]
+    fn test_generator_is_reproducible() {
+        let generator = Generator {
+            seed: 42,
+            near_duplicate_rate: 0.5,
+            ..Default::default()
+        };
+        let records = generator.generate().unwrap();
+        assert_eq!(records, generator.generate().unwrap());
+        assert!(records.len() >= 10 && records.len() <= 30);
+        assert!(records.iter().any(|r| r.label == Label::Near));
+
+        let other = Generator {
+            seed: 43,
+            ..generator
+        };
+        assert_ne!(records, other.generate().unwrap());
+
+        for rate in [-0.1, 1.5, f64::NAN] {
+            let invalid = Generator {
+                near_duplicate_rate: rate,
+                ..Default::default()
+            };
+            assert!(invalid.generate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_near_duplicates_differ_from_their_source() {
+        let generator = Generator {
+            seed: 3,
+            corpus: vec!["OLD MAN".to_string(), "1942".to_string(), "aa".to_string()],
+            copies: Copies::Weighted(vec![0.0, 0.0, 0.0, 1.0]),
+            near_duplicate_rate: 1.0,
+            mutations: vec![Mutation::Casing, Mutation::Typo],
+        };
+        let records = generator.generate().unwrap();
+        for record in &records {
+            let source = &generator.corpus[record.source];
+            assert_eq!(record.label == Label::Near, record.text != *source);
+        }
+        // Casing never changes digits, but typos always can
+        assert!(records.iter().any(|r| r.label == Label::Exact));
+        assert!(records.iter().any(|r| r.label == Label::Near));
+    }
+
+    #[test]
+    fn test_evaluate_exact_detector() {
+        let generator = Generator {
+            seed: 7,
+            copies: Copies::Weighted(vec![0.0, 0.0, 1.0]),
+            near_duplicate_rate: 1.0,
+            mutations: vec![Mutation::Punctuation],
+            ..Default::default()
+        };
+        let records = generator.generate().unwrap();
+        let texts: Vec<&str> = records.iter().map(|r| r.text.as_str()).collect();
+        let evaluation = evaluate(&records, &analyze_duplicates(&texts));
+
+        // Three copies per phrase, two of them with different trailing punctuation
+        assert_eq!(records.len(), 30);
+        assert_eq!(evaluation.false_negatives + evaluation.true_positives, 30);
+        assert!(evaluation.recall < 1.0);
+    }
+}
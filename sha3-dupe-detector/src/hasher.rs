@@ -0,0 +1,237 @@
+/*
+Pluggable hash algorithms.
+
+Every algorithm implements DupeHasher. Digests are fixed-size arrays, so
+maps and sets of digests never allocate per record. SHA3-256 is the default;
+SHA3-512 and BLAKE3 are cryptographic alternatives, and XXH3-128 trades
+collision resistance for speed, which is fine for trusted internal data.
+
+Algorithm names the implementations so one can be chosen at runtime, and the
+with_hasher! macro turns that choice back into a concrete hasher type.
+*/
+
+use sha3::{Digest, Sha3_256, Sha3_512};
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::str::FromStr;
+use xxhash_rust::xxh3::{xxh3_128, Xxh3};
+
+pub trait DupeHasher: Sync {
+    type Digest: AsRef<[u8]>
+        + for<'a> TryFrom<&'a [u8]>
+        + Copy
+        + fmt::Debug
+        + Ord
+        + Hash
+        + Send
+        + Sync;
+
+    fn name(&self) -> &'static str;
+
+    fn digest(&self, data: &[u8]) -> Self::Digest;
+
+    // Hash everything the reader yields without buffering it all in memory
+    fn digest_reader<R: Read>(&self, reader: R) -> io::Result<Self::Digest>;
+}
+
+pub struct Sha3_256Hasher;
+pub struct Sha3_512Hasher;
+pub struct Blake3Hasher;
+pub struct Xxh3Hasher;
+
+impl DupeHasher for Sha3_256Hasher {
+    type Digest = [u8; 32];
+
+    fn name(&self) -> &'static str {
+        "sha3-256"
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        Sha3_256::digest(data).into()
+    }
+
+    fn digest_reader<R: Read>(&self, mut reader: R) -> io::Result<[u8; 32]> {
+        let mut hasher = Sha3_256::new();
+        io::copy(&mut reader, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+}
+
+impl DupeHasher for Sha3_512Hasher {
+    type Digest = [u8; 64];
+
+    fn name(&self) -> &'static str {
+        "sha3-512"
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 64] {
+        Sha3_512::digest(data).into()
+    }
+
+    fn digest_reader<R: Read>(&self, mut reader: R) -> io::Result<[u8; 64]> {
+        let mut hasher = Sha3_512::new();
+        io::copy(&mut reader, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+}
+
+impl DupeHasher for Blake3Hasher {
+    type Digest = [u8; 32];
+
+    fn name(&self) -> &'static str {
+        "blake3"
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        blake3::hash(data).into()
+    }
+
+    fn digest_reader<R: Read>(&self, mut reader: R) -> io::Result<[u8; 32]> {
+        let mut hasher = blake3::Hasher::new();
+        io::copy(&mut reader, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+}
+
+impl DupeHasher for Xxh3Hasher {
+    type Digest = [u8; 16];
+
+    fn name(&self) -> &'static str {
+        "xxh3-128"
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 16] {
+        xxh3_128(data).to_be_bytes()
+    }
+
+    fn digest_reader<R: Read>(&self, mut reader: R) -> io::Result<[u8; 16]> {
+        let mut hasher = Xxh3::new();
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hasher.digest128().to_be_bytes())
+    }
+}
+
+// The hash algorithms that can be selected at runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Sha3_256,
+    Sha3_512,
+    Blake3,
+    Xxh3,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 4] = [
+        Algorithm::Sha3_256,
+        Algorithm::Sha3_512,
+        Algorithm::Blake3,
+        Algorithm::Xxh3,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha3_256 => Sha3_256Hasher.name(),
+            Algorithm::Sha3_512 => Sha3_512Hasher.name(),
+            Algorithm::Blake3 => Blake3Hasher.name(),
+            Algorithm::Xxh3 => Xxh3Hasher.name(),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "xxh3" | "xxhash" => return Ok(Algorithm::Xxh3),
+            "sha3" => return Ok(Algorithm::Sha3_256),
+            _ => {}
+        }
+        Algorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Algorithm::ALL.iter().map(|a| a.name()).collect();
+                format!(
+                    "unknown algorithm '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/*
+Runs an expression with `$hasher` bound to the hasher selected by an Algorithm:
+
+    let report = with_hasher!(algorithm, hasher => analyze_duplicates_with(&phrases, &hasher));
+*/
+#[macro_export]
+macro_rules! with_hasher {
+    ($algorithm:expr, $hasher:ident => $body:expr) => {
+        match $algorithm {
+            $crate::hasher::Algorithm::Sha3_256 => {
+                let $hasher = $crate::hasher::Sha3_256Hasher;
+                $body
+            }
+            $crate::hasher::Algorithm::Sha3_512 => {
+                let $hasher = $crate::hasher::Sha3_512Hasher;
+                $body
+            }
+            $crate::hasher::Algorithm::Blake3 => {
+                let $hasher = $crate::hasher::Blake3Hasher;
+                $body
+            }
+            $crate::hasher::Algorithm::Xxh3 => {
+                let $hasher = $crate::hasher::Xxh3Hasher;
+                $body
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_reader_matches_digest<H: DupeHasher>(hasher: H) {
+        let data = "the sail was patched with flour sacks".repeat(5000);
+        assert_eq!(
+            hasher.digest(data.as_bytes()),
+            hasher.digest_reader(data.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_digest_reader_matches_digest() {
+        check_reader_matches_digest(Sha3_256Hasher);
+        check_reader_matches_digest(Sha3_512Hasher);
+        check_reader_matches_digest(Blake3Hasher);
+        check_reader_matches_digest(Xxh3Hasher);
+    }
+
+    #[test]
+    fn test_algorithm_from_str() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(algorithm.name().parse(), Ok(algorithm));
+        }
+        assert_eq!("XXHASH".parse(), Ok(Algorithm::Xxh3));
+        assert!("md5".parse::<Algorithm>().is_err());
+    }
+}
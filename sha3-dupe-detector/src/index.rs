@@ -0,0 +1,278 @@
+/*
+Persistent digest index for incremental dedup across runs.
+
+The index is an on-disk sorted table of fixed-size digests. Opening it loads
+the table into memory; lookups are binary searches. Digests added during a
+run are kept in a separate set until save merges them into the table and
+rewrites the file, so a later batch can ask whether a record was seen in any
+earlier batch.
+
+File layout (integers are little-endian):
+
+    b"DUPEIDX1"            magic and format version
+    u8 + bytes             name of the hash algorithm
+    u8                     digest length in bytes
+    u64                    number of digests
+    digests                sorted, concatenated
+
+An index only makes sense with the algorithm it was built with, so opening
+it with a different hasher is an error.
+*/
+
+use crate::hasher::DupeHasher;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"DUPEIDX1";
+
+pub struct DigestIndex<D> {
+    algorithm: String,
+    // Digests from earlier runs, sorted
+    history: Vec<D>,
+    // Digests added since the index was opened
+    added: HashSet<D>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<D> DigestIndex<D>
+where
+    D: AsRef<[u8]> + for<'a> TryFrom<&'a [u8]> + Copy + Ord + Hash,
+{
+    // An empty index for the given hasher
+    pub fn new<H: DupeHasher<Digest = D>>(hasher: &H) -> Self {
+        DigestIndex {
+            algorithm: hasher.name().to_string(),
+            history: Vec::new(),
+            added: HashSet::new(),
+        }
+    }
+
+    // Load an index from disk, or start an empty one when the file does not exist yet
+    pub fn open<H: DupeHasher<Digest = D>>(path: &Path, hasher: &H) -> io::Result<Self> {
+        let mut index = DigestIndex::new(hasher);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(index),
+            Err(err) => return Err(err),
+        };
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid(format!("{} is not a digest index", path.display())));
+        }
+
+        let mut len = [0; 1];
+        reader.read_exact(&mut len)?;
+        let mut name = vec![0; len[0] as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name);
+        if name != index.algorithm {
+            return Err(invalid(format!(
+                "{} was built with {}, not {}",
+                path.display(),
+                name,
+                index.algorithm
+            )));
+        }
+
+        let mut digest_len = [0; 1];
+        reader.read_exact(&mut digest_len)?;
+        let digest_len = digest_len[0] as usize;
+        let mut count = [0; 8];
+        reader.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count);
+
+        // An empty index is saved with a digest length of 0
+        let expected_len = hasher.digest(b"").as_ref().len();
+        if digest_len != expected_len && !(digest_len == 0 && count == 0) {
+            return Err(invalid(format!(
+                "{} has {}-byte digests, {} makes {}-byte digests",
+                path.display(),
+                digest_len,
+                index.algorithm,
+                expected_len
+            )));
+        }
+        // The header is trusted no further than the bytes that follow it
+        let header_len = (MAGIC.len() + 1 + len[0] as usize + 1 + 8) as u64;
+        if count.checked_mul(digest_len as u64) != Some(file_len.saturating_sub(header_len)) {
+            return Err(invalid(format!(
+                "{} should hold {} digests but is {} bytes long",
+                path.display(),
+                count,
+                file_len
+            )));
+        }
+        let count = count as usize;
+
+        let mut buf = vec![0; digest_len];
+        index.history.reserve(count);
+        for _ in 0..count {
+            reader.read_exact(&mut buf)?;
+            let digest = D::try_from(&buf[..])
+                .map_err(|_| invalid(format!("{} has the wrong digest size", path.display())))?;
+            index.history.push(digest);
+        }
+        if index.history.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(invalid(format!("{} is not sorted", path.display())));
+        }
+
+        Ok(index)
+    }
+
+    pub fn algorithm(&self) -> &str {
+        &self.algorithm
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len() + self.added.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Whether the digest was saved by an earlier run
+    pub fn seen_before(&self, digest: &D) -> bool {
+        self.history.binary_search(digest).is_ok()
+    }
+
+    pub fn contains(&self, digest: &D) -> bool {
+        self.seen_before(digest) || self.added.contains(digest)
+    }
+
+    // Add a digest, returns false when it was already known
+    pub fn insert(&mut self, digest: D) -> bool {
+        !self.seen_before(&digest) && self.added.insert(digest)
+    }
+
+    // Merge the new digests into the table and write it to disk
+    pub fn save(&mut self, path: &Path) -> io::Result<()> {
+        let mut added: Vec<D> = self.added.drain().collect();
+        added.sort_unstable();
+        let mut merged = Vec::with_capacity(self.history.len() + added.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.history.len() && j < added.len() {
+            if self.history[i] < added[j] {
+                merged.push(self.history[i]);
+                i += 1;
+            } else {
+                merged.push(added[j]);
+                j += 1;
+            }
+        }
+        merged.extend_from_slice(&self.history[i..]);
+        merged.extend_from_slice(&added[j..]);
+        self.history = merged;
+
+        // Write next to the target and rename, so a crash never leaves half an index
+        let tmp = temp_path(path);
+        {
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            writer.write_all(MAGIC)?;
+            writer.write_all(&[self.algorithm.len() as u8])?;
+            writer.write_all(self.algorithm.as_bytes())?;
+            let digest_len = self.history.first().map_or(0, |d| d.as_ref().len());
+            writer.write_all(&[digest_len as u8])?;
+            writer.write_all(&(self.history.len() as u64).to_le_bytes())?;
+            for digest in &self.history {
+                writer.write_all(digest.as_ref())?;
+            }
+            writer.flush()?;
+        }
+        fs::rename(tmp, path)
+    }
+}
+
+// The path with ".tmp" added to its file name, so it never names the path itself
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::{Sha3_256Hasher, Xxh3Hasher};
+
+    #[test]
+    fn test_index_persists_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.idx");
+        let hasher = Sha3_256Hasher;
+
+        let mut index = DigestIndex::open(&path, &hasher).unwrap();
+        assert!(index.is_empty());
+        assert!(index.insert(hasher.digest(b"monday")));
+        assert!(!index.insert(hasher.digest(b"monday")));
+        index.save(&path).unwrap();
+
+        let mut index = DigestIndex::open(&path, &hasher).unwrap();
+        assert!(index.seen_before(&hasher.digest(b"monday")));
+        assert!(!index.insert(hasher.digest(b"monday")));
+        assert!(index.insert(hasher.digest(b"tuesday")));
+        index.save(&path).unwrap();
+
+        let index = DigestIndex::open(&path, &hasher).unwrap();
+        assert_eq!(index.len(), 2);
+        assert!(index.seen_before(&hasher.digest(b"tuesday")));
+
+        assert!(DigestIndex::open(&path, &Xxh3Hasher).is_err());
+    }
+
+    #[test]
+    fn test_temp_path_keeps_the_file_name() {
+        assert_eq!(
+            temp_path(Path::new("a/seen.tmp")),
+            Path::new("a/seen.tmp.tmp")
+        );
+        assert_ne!(
+            temp_path(Path::new("seen.idx")),
+            temp_path(Path::new("seen.json"))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.tmp");
+        let hasher = Sha3_256Hasher;
+        let mut index = DigestIndex::new(&hasher);
+        index.insert(hasher.digest(b"monday"));
+        index.save(&path).unwrap();
+        assert_eq!(DigestIndex::open(&path, &hasher).unwrap().len(), 1);
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn test_open_rejects_corrupt_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.idx");
+        let hasher = Sha3_256Hasher;
+        let header = |digest_len: u8, count: u64| {
+            let mut bytes = MAGIC.to_vec();
+            bytes.push(hasher.name().len() as u8);
+            bytes.extend_from_slice(hasher.name().as_bytes());
+            bytes.push(digest_len);
+            bytes.extend_from_slice(&count.to_le_bytes());
+            bytes
+        };
+        let open = |bytes: Vec<u8>| {
+            fs::write(&path, bytes).unwrap();
+            DigestIndex::open(&path, &hasher).err().unwrap().kind()
+        };
+
+        assert_eq!(open(header(32, u64::MAX)), io::ErrorKind::InvalidData);
+        assert_eq!(open(header(32, 2)), io::ErrorKind::InvalidData);
+        let mut short_digests = header(16, 1);
+        short_digests.extend_from_slice(&[0; 16]);
+        assert_eq!(open(short_digests), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,10 +1,33 @@
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
 use rand::Rng;
-use sha3::Digest;
-use sha3::Sha3_256;
+use rayon::prelude::*;
 use std::collections::HashMap;
 
+pub mod csv_dedup;
+pub mod fuzzy;
+pub mod generator;
+pub mod hasher;
+pub mod index;
+pub mod normalise;
+pub mod report;
+pub mod scan;
+pub mod stream;
+
+pub use fuzzy::MinHashConfig;
+pub use hasher::{Algorithm, DupeHasher};
+pub use normalise::Normaliser;
+pub use report::{DuplicateGroup, DuplicateReport};
+
+// How the scan, lines and csv commands match records beyond their exact bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchOptions {
+    // Normalisers to run before hashing, in order
+    pub normalise: Vec<Normaliser>,
+    // Also look for near-duplicates with MinHash
+    pub fuzzy: Option<MinHashConfig>,
+}
+
 // List of phrases
 static PHRASES: [&str; 10] = [
     "man can be destroyed but not defeated",
@@ -37,31 +60,182 @@ pub fn generate_random_phrases() -> Vec<&'static str> {
     phrases
 }
 
-// Analyze duplicates
-pub fn analyze_duplicates(phrases: &[&str]) {
-    let mut hashes: HashMap<_, (usize, &str)> = HashMap::new();
-    println!("Total number of phrases: {}", phrases.len());
+// Analyze duplicates with SHA3-256
+pub fn analyze_duplicates(phrases: &[&str]) -> DuplicateReport {
+    analyze_duplicates_with(phrases, &hasher::Sha3_256Hasher)
+}
+
+// Analyze duplicates with any hash algorithm.
+// Records are hashed on all cores: every rayon task fills its own map of
+// hash -> positions and the maps are merged at the end. Positions are sorted
+// afterwards, so the report does not depend on the number of threads.
+pub fn analyze_duplicates_with<H: DupeHasher>(phrases: &[&str], hasher: &H) -> DuplicateReport {
+    let hashes: HashMap<H::Digest, Vec<usize>> = phrases
+        .par_iter()
+        .enumerate()
+        .fold(HashMap::new, |mut hashes, (position, phrase)| {
+            let hash = hasher.digest(phrase.as_bytes());
+            hashes.entry(hash).or_insert_with(Vec::new).push(position);
+            hashes
+        })
+        .reduce(HashMap::new, merge_positions);
+
+    let mut report = DuplicateReport {
+        algorithm: hasher.name().to_string(),
+        total_records: phrases.len(),
+        unique_records: hashes.len(),
+        ..Default::default()
+    };
+
+    for (hash, mut positions) in hashes {
+        let count = positions.len();
+        if count > 1 {
+            positions.sort_unstable();
+            let first_seen = positions[0];
+            report.duplicate_groups += 1;
+            report.duplicate_records += count - 1; // subtract one to exclude the original
+            report.groups.push(DuplicateGroup {
+                hash: hex::encode(hash),
+                count,
+                first_seen,
+                positions,
+                record: phrases[first_seen].to_string(),
+                normalisations: Vec::new(),
+            });
+        }
+    }
+    report.groups.sort_by_key(|group| group.first_seen);
+
+    report
+}
+
+// Analyze duplicates after running every record through a normalisation pipeline.
+// Group hashes are digests of the normalised text; groups keep the original
+// text of their first record and list the normalisers that made different
+// originals collide.
+pub fn analyze_normalised_with<H: DupeHasher>(
+    phrases: &[&str],
+    steps: &[Normaliser],
+    hasher: &H,
+) -> DuplicateReport {
+    let normalised: Vec<String> = phrases
+        .par_iter()
+        .map(|phrase| normalise::normalise(phrase, steps))
+        .collect();
+    let normalised: Vec<&str> = normalised.iter().map(String::as_str).collect();
+
+    let mut report = analyze_duplicates_with(&normalised, hasher);
+    for group in &mut report.groups {
+        let originals: Vec<&str> = group.positions.iter().map(|&p| phrases[p]).collect();
+        group.record = phrases[group.first_seen].to_string();
+        group.normalisations = normalise::explain_collision(&originals, steps)
+            .iter()
+            .map(|step| step.name().to_string())
+            .collect();
+    }
+    report
+}
 
-    for phrase in phrases {
-        let hash = Sha3_256::digest(phrase.as_bytes());
-        let entry = hashes.entry(hash).or_insert((0, phrase));
-        entry.0 += 1;
+// Merge two partial maps of hash -> positions, keeping the larger one as the base
+fn merge_positions<K: std::hash::Hash + Eq>(
+    mut a: HashMap<K, Vec<usize>>,
+    mut b: HashMap<K, Vec<usize>>,
+) -> HashMap<K, Vec<usize>> {
+    if a.len() < b.len() {
+        std::mem::swap(&mut a, &mut b);
     }
+    for (hash, mut positions) in b {
+        a.entry(hash).or_default().append(&mut positions);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_duplicates_report() {
+        let phrases = ["a", "b", "a", "c", "b", "a"];
+        let report = analyze_duplicates(&phrases);
+
+        assert_eq!(report.total_records, 6);
+        assert_eq!(report.unique_records, 3);
+        assert_eq!(report.duplicate_groups, 2);
+        assert_eq!(report.duplicate_records, 3);
+        assert_eq!(report.groups[0].record, "a");
+        assert_eq!(report.groups[0].positions, vec![0, 2, 5]);
+        assert_eq!(report.groups[1].first_seen, 1);
+        assert_eq!(report.algorithm, "sha3-256");
 
-    let total_unique_phrases = hashes.len();
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("hash,count,first_seen,positions,record,normalisations\n"));
+        assert!(csv.contains(",3,0,0;2;5,a,\n"));
 
-    let mut total_unique_duplicates = 0;
-    let mut total_combined_duplicates = 0;
+        let mut csv = Vec::new();
+        analyze_duplicates(&["a", "b"]).write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "hash,count,first_seen,positions,record,normalisations\n"
+        );
+    }
 
-    for (hash, (count, phrase)) in &hashes {
-        if *count > 1 {
-            total_unique_duplicates += 1;
-            total_combined_duplicates += count - 1; // subtract one to exclude the original
-            println!("{} - {} times: {}", hex::encode(hash), count, phrase);
+    #[test]
+    fn test_analyze_duplicates_with_every_algorithm() {
+        let phrases = ["a", "b", "a", "c", "b", "a"];
+        let expected = analyze_duplicates(&phrases);
+        for algorithm in Algorithm::ALL {
+            let report = with_hasher!(algorithm, h => analyze_duplicates_with(&phrases, &h));
+            assert_eq!(report.algorithm, algorithm.name());
+            assert_eq!(report.duplicate_records, expected.duplicate_records);
+            assert_eq!(report.groups.len(), expected.groups.len());
         }
     }
 
-    println!("Total Unique Phrases: {}", total_unique_phrases);
-    println!("Total Unique Duplicates: {}", total_unique_duplicates);
-    println!("Total Combined Duplicates: {}", total_combined_duplicates);
+    #[test]
+    fn test_analyze_normalised_reports_collisions() {
+        let phrases = [
+            "The old man",
+            "the old man ",
+            "a man can be destroyed but not defeated",
+            "man can be destroyed but not defeated",
+            "the old man",
+        ];
+        let steps = [
+            Normaliser::CaseFold,
+            Normaliser::Whitespace,
+            Normaliser::StopWords,
+        ];
+        let report = analyze_normalised_with(&phrases, &steps, &hasher::Sha3_256Hasher);
+
+        assert_eq!(report.duplicate_groups, 2);
+        assert_eq!(report.groups[0].record, "The old man");
+        assert_eq!(report.groups[0].positions, vec![0, 1, 4]);
+        assert_eq!(report.groups[0].normalisations, vec!["stop-words"]);
+        assert_eq!(report.groups[1].normalisations, vec!["stop-words"]);
+        assert!(analyze_duplicates(&phrases).groups.is_empty());
+    }
+
+    #[test]
+    fn test_analyze_duplicates_is_deterministic_across_threads() {
+        let records: Vec<String> = (0..5000).map(|i| format!("record {}", i % 700)).collect();
+        let records: Vec<&str> = records.iter().map(String::as_str).collect();
+
+        let reports: Vec<DuplicateReport> = [1, 2, 8]
+            .into_iter()
+            .map(|threads| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                pool.install(|| analyze_duplicates(&records))
+            })
+            .collect();
+
+        assert_eq!(reports[0], reports[1]);
+        assert_eq!(reports[0], reports[2]);
+        assert_eq!(reports[0].groups[0].positions.len(), 8);
+    }
 }
@@ -4,7 +4,7 @@ and prints the number of unique phrases and the number of duplicate phrases.
 
 Example output:
 
-Total number of phrases: 24
+Total number of records: 24
 131a931202f9f1e7821ece767d0a9041aeb0270a40def3583de149c849683cb2 - 3 times: the old man looked at him with his sun burned confident loving eyes
 ad604cf092d30c844c8f1820de47771efef7a66763468cd2a68bbed8637579d2 - 3 times: his eyes were cheerful and undefeated
 796599a1f14554fde9514bf41dca747548570d3a7a38a74cc19caa07ae55ca70 - 2 times: a man can be destroyed but not defeated
@@ -14,14 +14,572 @@ ad604cf092d30c844c8f1820de47771efef7a66763468cd2a68bbed8637579d2 - 3 times: his
 7f7f417bb4ff8b62b19edc25c3e359c6a32f7d2f06883ce40062eae093a23ad1 - 3 times: the old man had taught the boy to fish
 c33fc7b48db132dfdc5fc6aac5514ad867fc35b21c06e55725720057034c6a56 - 2 times: he was an old man who fished alone
 d40a2c3a380fbb5f5b15db16a9acb87b14c91906aac595e6f920827ee6187ef6 - 2 times: the sail was patched with flour sacks
-Total Unique Phrases: 10
+Total Unique Records: 10
 Total Unique Duplicates: 9
 Total Combined Duplicates: 14
 
+To find duplicate files in one or more directory trees:
+
+cargo run -- scan /data/landing /data/archive
+
+Each group lists the digest, the number of copies, the file size and
+the bytes wasted by the extra copies, followed by the paths.
+
+Reports can also be written as JSON or CSV, and the exit code can be used
+to fail a pipeline step when duplicates are found:
+
+cargo run -- --format json scan /data/landing
+cargo run -- --format csv --fail-on-duplicates scan /data/landing > audit.csv
+
+To dedup a large text file line by line without loading it into memory
+(reads stdin when no file is given, writes to stdout):
+
+cargo run -- lines export.csv > export.dedup.csv
+cat app.log | cargo run -- lines --duplicates
+
+With --index the digests are kept in a file between runs, so each daily load
+only passes lines that were never seen in an earlier batch:
+
+cargo run -- lines --index history.idx 2023-08-02.csv > new-rows.csv
+
+To dedup CSV rows on a subset of columns, report the groups by row number
+(1 is the first row after the header) and write one row per key:
+
+cargo run -- csv customers.csv --key name,city --keep most-complete --output deduped.csv
+
+To also cluster near-duplicate phrases with MinHash next to the exact groups:
+
+cargo run -- phrases --fuzzy --threshold 0.8
+
+--fuzzy works with the other commands as well: scan lists files with similar
+text, csv lists similar keys, and lines also drops lines that are close to a
+line it already wrote (keeping every written line's signature in memory):
+
+cargo run -- scan --fuzzy /data/landing
+cat app.log | cargo run -- lines --fuzzy --threshold 0.9
+
+Records can be normalised before hashing; each group then lists the
+normalisers that made its records collide:
+
+cargo run -- phrases --normalise nfkc,case-fold,whitespace,punctuation,stop-words
+
+--normalise works with the other commands as well. Lines and files are
+compared after the pipeline but written out as they are, and csv runs it
+over each key value:
+
+cargo run -- lines --normalise case-fold,whitespace export.csv > export.dedup.csv
+cargo run -- csv customers.csv --key name,city --normalise punctuation
+
+Every command hashes with SHA3-256 unless another algorithm is chosen
+(sha3-256, sha3-512, blake3 or xxh3-128). XXH3 is not cryptographic but is
+much faster, which is fine for trusted internal data:
+
+cargo run -- --algorithm xxh3 scan /data/landing
+
+To produce reproducible, labelled test data with exact and near-duplicates
+(typos, casing, whitespace and punctuation), from the built-in phrases or a
+corpus file with one record per line:
+
+cargo run -- generate --seed 42 --corpus titles.txt --max-copies 5 --near-rate 0.3 > labelled.csv
+
+The same options evaluate the detector against the generated labels and
+print the pairwise precision and recall:
+
+cargo run -- evaluate --seed 42 --near-rate 0.3 --normalise case-fold,whitespace --fuzzy
 */
-use sha3_dupe_detector::generate_random_phrases;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use sha3_dupe_detector::csv_dedup::{analyze_csv_matching, write_deduplicated_file, Keep};
+use sha3_dupe_detector::fuzzy::{find_near_duplicates, MinHashConfig};
+use sha3_dupe_detector::generator::{evaluate, Copies, Evaluation, Generator, Mutation};
+use sha3_dupe_detector::index::DigestIndex;
+use sha3_dupe_detector::scan::{scan_paths_matching, FileScan};
+use sha3_dupe_detector::stream::{dedup_lines_matching, StreamMode, StreamStats};
+use sha3_dupe_detector::DupeHasher;
+use sha3_dupe_detector::{
+    analyze_normalised_with, generate_random_phrases, with_hasher, Algorithm, DuplicateReport,
+    MatchOptions, Normaliser,
+};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+use std::process;
+
+/// CLI tool to detect duplicates with SHA3 hashes
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Output format of the report
+    #[arg(short, long, value_enum, default_value = "text", global = true)]
+    format: Format,
+
+    /// Hash algorithm: sha3-256, sha3-512, blake3 or xxh3-128
+    #[arg(short, long, default_value_t = Algorithm::Sha3_256, global = true)]
+    algorithm: Algorithm,
+
+    /// Exit with status 1 when any duplicate is found
+    #[arg(long, global = true)]
+    fail_on_duplicates: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Analyze a shuffled list of duplicated phrases (the default)
+    Phrases {
+        /// Comma separated normalisers to run before hashing:
+        /// nfkc, case-fold, whitespace, punctuation, stop-words
+        #[arg(short, long, value_delimiter = ',')]
+        normalise: Vec<Normaliser>,
+
+        #[command(flatten)]
+        fuzzy: FuzzyArgs,
+    },
+    /// Find files with identical contents in one or more directory trees
+    Scan {
+        /// Files or directories to scan
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Comma separated normalisers to run before hashing:
+        /// nfkc, case-fold, whitespace, punctuation, stop-words
+        #[arg(short, long, value_delimiter = ',')]
+        normalise: Vec<Normaliser>,
+
+        #[command(flatten)]
+        fuzzy: FuzzyArgs,
+    },
+    /// Stream a text file (or stdin) and write only the first occurrence of each line
+    Lines {
+        /// File to read, stdin when omitted
+        path: Option<PathBuf>,
+
+        /// Write only the lines that repeat an earlier line
+        #[arg(short, long)]
+        duplicates: bool,
+
+        /// Digest index of earlier runs, created when missing and updated with new lines
+        #[arg(short, long)]
+        index: Option<PathBuf>,
+
+        /// Comma separated normalisers to run before hashing:
+        /// nfkc, case-fold, whitespace, punctuation, stop-words
+        #[arg(short, long, value_delimiter = ',')]
+        normalise: Vec<Normaliser>,
+
+        #[command(flatten)]
+        fuzzy: FuzzyArgs,
+    },
+    /// Find CSV rows that share the same values in the key columns
+    Csv {
+        /// CSV file with a header row
+        path: PathBuf,
+
+        /// Comma separated columns that define the identity of a row
+        #[arg(short, long, value_delimiter = ',', required = true)]
+        key: Vec<String>,
+
+        /// Which row of each group to keep: first, last or most-complete
+        #[arg(long, default_value_t = Keep::First)]
+        keep: Keep,
+
+        /// Write the deduplicated CSV to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Comma separated normalisers to run before hashing:
+        /// nfkc, case-fold, whitespace, punctuation, stop-words
+        #[arg(short, long, value_delimiter = ',')]
+        normalise: Vec<Normaliser>,
+
+        #[command(flatten)]
+        fuzzy: FuzzyArgs,
+    },
+    /// Write seeded, labelled synthetic records as CSV: text,source,label,mutation
+    Generate {
+        #[command(flatten)]
+        generator: GeneratorArgs,
+    },
+    /// Measure precision and recall of the detector on generated records
+    Evaluate {
+        #[command(flatten)]
+        generator: GeneratorArgs,
+
+        /// Comma separated normalisers to run before hashing
+        #[arg(short, long, value_delimiter = ',')]
+        normalise: Vec<Normaliser>,
+
+        #[command(flatten)]
+        fuzzy: FuzzyArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+struct GeneratorArgs {
+    /// Seed of the random generator, the same seed gives the same records
+    #[arg(long, default_value = "0")]
+    seed: u64,
+
+    /// File with one record per line, the built-in phrases when omitted
+    #[arg(long)]
+    corpus: Option<PathBuf>,
+
+    /// Minimum number of copies of each record
+    #[arg(long, default_value = "1")]
+    min_copies: usize,
+
+    /// Maximum number of copies of each record
+    #[arg(long, default_value = "3")]
+    max_copies: usize,
+
+    /// Comma separated relative weights of 1, 2, 3, ... copies, overrides min and max
+    #[arg(long, value_delimiter = ',')]
+    copy_weights: Vec<f64>,
+
+    /// Chance that a copy is a near-duplicate instead of an exact one
+    #[arg(long, default_value = "0")]
+    near_rate: f64,
+
+    /// Comma separated mutations for near-duplicates:
+    /// typo, casing, whitespace, punctuation
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "typo,casing,whitespace,punctuation"
+    )]
+    mutations: Vec<Mutation>,
+}
+
+impl GeneratorArgs {
+    fn generator(&self) -> Result<Generator, Box<dyn Error>> {
+        let mut generator = Generator {
+            seed: self.seed,
+            copies: if self.copy_weights.is_empty() {
+                Copies::Uniform {
+                    min: self.min_copies,
+                    max: self.max_copies,
+                }
+            } else {
+                Copies::Weighted(self.copy_weights.clone())
+            },
+            near_duplicate_rate: self.near_rate,
+            mutations: self.mutations.clone(),
+            ..Default::default()
+        };
+        if let Some(corpus) = &self.corpus {
+            generator.load_corpus(corpus)?;
+        }
+        Ok(generator)
+    }
+}
+
+#[derive(Args, Debug, Default)]
+struct FuzzyArgs {
+    /// Also cluster near-duplicates with MinHash
+    #[arg(long)]
+    fuzzy: bool,
+
+    /// Minimum Jaccard similarity for near-duplicates, between 0 and 1
+    #[arg(long, default_value = "0.8", value_parser = parse_threshold)]
+    threshold: f64,
+
+    /// Number of hash functions in each MinHash signature
+    #[arg(long, default_value = "128", value_parser = parse_positive)]
+    num_hashes: usize,
+
+    /// Number of characters per shingle
+    #[arg(long, default_value = "4", value_parser = parse_positive)]
+    shingle_size: usize,
+}
+
+fn parse_threshold(s: &str) -> Result<f64, String> {
+    let threshold: f64 = s.parse().map_err(|err| format!("{}", err))?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("{} is not between 0 and 1", threshold));
+    }
+    Ok(threshold)
+}
+
+fn parse_positive(s: &str) -> Result<usize, String> {
+    let n: usize = s.parse().map_err(|err| format!("{}", err))?;
+    if n == 0 {
+        return Err("must be at least 1".to_string());
+    }
+    Ok(n)
+}
+
+impl FuzzyArgs {
+    fn config(&self) -> Option<MinHashConfig> {
+        self.fuzzy.then_some(MinHashConfig {
+            num_hashes: self.num_hashes,
+            shingle_size: self.shingle_size,
+            threshold: self.threshold,
+        })
+    }
+
+    fn options(&self, normalise: Vec<Normaliser>) -> MatchOptions {
+        MatchOptions {
+            normalise,
+            fuzzy: self.config(),
+        }
+    }
+}
+
+fn print_file_scan(scan: &FileScan) {
+    for group in &scan.groups {
+        println!(
+            "{} - {} copies of {} bytes, {} bytes wasted",
+            group.hash,
+            group.paths.len(),
+            group.size,
+            group.wasted_bytes()
+        );
+        for path in &group.paths {
+            println!("    {}", path.display());
+        }
+    }
+    for cluster in &scan.near_duplicates {
+        println!(
+            "Similar files (similarity >= {:.2}):",
+            cluster.min_similarity
+        );
+        for path in &cluster.paths {
+            println!("    {}", path.display());
+        }
+    }
+    for error in &scan.errors {
+        eprintln!("Skipped: {}", error);
+    }
+
+    println!("Hash algorithm: {}", scan.algorithm);
+    println!("Total Files Scanned: {}", scan.total_files);
+    println!("Total Bytes Scanned: {}", scan.total_bytes);
+    println!("Total Duplicate Groups: {}", scan.groups.len());
+    println!("Total Duplicate Files: {}", scan.duplicate_files());
+    println!("Total Wasted Bytes: {}", scan.wasted_bytes());
+    if !scan.near_duplicates.is_empty() {
+        println!(
+            "Total Similar File Clusters: {}",
+            scan.near_duplicates.len()
+        );
+    }
+}
+
+fn print_report(report: &DuplicateReport, format: Format) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => println!("{}", report),
+        Format::Json => println!("{}", report.to_json()?),
+        Format::Csv => report.write_csv(io::stdout())?,
+    }
+    Ok(())
+}
+
+fn print_scan(scan: &FileScan, format: Format) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => print_file_scan(scan),
+        Format::Json => println!("{}", scan.to_json()?),
+        Format::Csv => scan.write_csv(io::stdout())?,
+    }
+    Ok(())
+}
+
+fn print_evaluation(evaluation: &Evaluation, format: Format) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => {
+            println!("True Positive Pairs: {}", evaluation.true_positives);
+            println!("False Positive Pairs: {}", evaluation.false_positives);
+            println!("False Negative Pairs: {}", evaluation.false_negatives);
+            println!("Precision: {:.4}", evaluation.precision);
+            println!("Recall: {:.4}", evaluation.recall);
+            println!("F1: {:.4}", evaluation.f1);
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(evaluation)?),
+        Format::Csv => {
+            let mut wtr = csv::Writer::from_writer(io::stdout());
+            wtr.serialize(evaluation)?;
+            wtr.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn filter_lines<H: DupeHasher>(
+    path: Option<PathBuf>,
+    mode: StreamMode,
+    index_path: Option<PathBuf>,
+    options: &MatchOptions,
+    hasher: &H,
+) -> Result<StreamStats, Box<dyn Error>> {
+    let mut index = match &index_path {
+        Some(index_path) => DigestIndex::open(index_path, hasher)?,
+        None => DigestIndex::new(hasher),
+    };
+    let stdout = BufWriter::new(io::stdout().lock());
+    let stats = match path {
+        Some(path) => {
+            let reader = BufReader::new(File::open(path)?);
+            dedup_lines_matching(reader, stdout, mode, hasher, &mut index, options)?
+        }
+        None => dedup_lines_matching(
+            io::stdin().lock(),
+            stdout,
+            mode,
+            hasher,
+            &mut index,
+            options,
+        )?,
+    };
+    if let Some(index_path) = index_path {
+        index.save(&index_path)?;
+    }
+    Ok(stats)
+}
+
+// Runs the selected command and returns whether duplicates were found
+fn run(cli: Cli) -> Result<bool, Box<dyn Error>> {
+    let command = cli.command.unwrap_or(Commands::Phrases {
+        normalise: Vec::new(),
+        fuzzy: FuzzyArgs::default(),
+    });
+    match command {
+        Commands::Phrases { normalise, fuzzy } => {
+            let phrases = generate_random_phrases();
+            let mut report = with_hasher!(
+                cli.algorithm,
+                h => analyze_normalised_with(&phrases, &normalise, &h)
+            );
+            if let Some(config) = fuzzy.config() {
+                report.near_duplicates = find_near_duplicates(&phrases, &config);
+            }
+            print_report(&report, cli.format)?;
+            Ok(report.has_duplicates())
+        }
+        Commands::Scan {
+            paths,
+            normalise,
+            fuzzy,
+        } => {
+            let options = fuzzy.options(normalise);
+            let scan = with_hasher!(cli.algorithm, h => scan_paths_matching(&paths, &h, &options));
+            print_scan(&scan, cli.format)?;
+            Ok(!scan.groups.is_empty())
+        }
+        Commands::Lines {
+            path,
+            duplicates,
+            index,
+            normalise,
+            fuzzy,
+        } => {
+            let mode = if duplicates {
+                StreamMode::Duplicates
+            } else {
+                StreamMode::Unique
+            };
+            let options = fuzzy.options(normalise);
+            let stats = with_hasher!(
+                cli.algorithm,
+                h => filter_lines(path, mode, index, &options, &h)
+            )?;
+            // stdout carries the data, so the summary goes to stderr
+            eprintln!(
+                "Lines read: {}, unique: {}, duplicates: {}, seen in earlier runs: {}, written: {}",
+                stats.lines_read,
+                stats.unique_lines,
+                stats.duplicate_lines(),
+                stats.previously_seen,
+                stats.lines_written
+            );
+            if options.fuzzy.is_some() {
+                eprintln!("Near duplicates dropped: {}", stats.near_duplicates);
+            }
+            Ok(stats.duplicate_lines() > 0)
+        }
+        Commands::Csv {
+            path,
+            key,
+            keep,
+            output,
+            normalise,
+            fuzzy,
+        } => {
+            let key: Vec<&str> = key.iter().map(String::as_str).collect();
+            let options = fuzzy.options(normalise);
+            let reader = BufReader::new(File::open(&path)?);
+            let dedup = with_hasher!(
+                cli.algorithm,
+                h => analyze_csv_matching(reader, &key, keep, &h, &options)
+            )?;
+            if let Some(output) = output {
+                write_deduplicated_file(&path, &output, &dedup.keep_rows)?;
+            }
+            print_report(&dedup.report, cli.format)?;
+            Ok(dedup.report.has_duplicates())
+        }
+        Commands::Generate { generator } => {
+            let generator = generator.generator()?;
+            let records = generator.generate()?;
+            let mut wtr = csv::Writer::from_writer(io::stdout().lock());
+            for record in &records {
+                wtr.serialize(record)?;
+            }
+            wtr.flush()?;
+            Ok(records.len() > generator.corpus.len())
+        }
+        Commands::Evaluate {
+            generator,
+            normalise,
+            fuzzy,
+        } => {
+            let records = generator.generator()?.generate()?;
+            let texts: Vec<&str> = records.iter().map(|r| r.text.as_str()).collect();
+            let mut report = with_hasher!(
+                cli.algorithm,
+                h => analyze_normalised_with(&texts, &normalise, &h)
+            );
+            if let Some(config) = fuzzy.config() {
+                report.near_duplicates = find_near_duplicates(&texts, &config);
+            }
+            print_evaluation(&evaluate(&records, &report), cli.format)?;
+            Ok(report.has_duplicates())
+        }
+    }
+}
 
 fn main() {
-    let phrases = generate_random_phrases();
-    sha3_dupe_detector::analyze_duplicates(&phrases);
+    let cli = Cli::parse();
+    let fail_on_duplicates = cli.fail_on_duplicates;
+    match run(cli) {
+        Ok(found) if found && fail_on_duplicates => process::exit(1),
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_args_reject_zero() {
+        for flag in ["--num-hashes", "--shingle-size"] {
+            let err = Cli::try_parse_from(["sha3-dupe-detector", "phrases", "--fuzzy", flag, "0"])
+                .unwrap_err();
+            assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+        }
+        assert!(parse_threshold("1.5").is_err());
+        assert_eq!(parse_positive("64"), Ok(64));
+    }
 }
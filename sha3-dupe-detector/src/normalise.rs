@@ -0,0 +1,179 @@
+/*
+Text normalisation before hashing.
+
+By default records are hashed exactly as they are, so "The old man" and
+"the old man " count as different. A pipeline of normalisers can be applied
+first; each step runs on the output of the previous one, in the order given:
+
+    nfkc         Unicode NFKC, so compatibility forms like "ﬁ" become "fi"
+    case-fold    lowercase everything
+    whitespace   trim and collapse runs of whitespace into a single space
+    punctuation  drop every character that is not alphanumeric or whitespace
+    stop-words   drop common English words such as "a", "the" and "is"
+
+explain_collision tells which steps made the records of a duplicate group
+collide. Steps often overlap (stop-word removal also collapses whitespace),
+so it drops steps one at a time in pipeline order while the originals still
+collide. What is left is minimal, no single step can be dropped, but it is
+not always the smallest such pipeline.
+*/
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+const STOP_WORDS: [&str; 40] = [
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "had", "has", "have",
+    "he", "her", "his", "i", "in", "is", "it", "its", "not", "of", "on", "or", "she", "so", "that",
+    "the", "their", "them", "there", "they", "this", "to", "was", "were", "with", "you",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normaliser {
+    Nfkc,
+    CaseFold,
+    Whitespace,
+    Punctuation,
+    StopWords,
+}
+
+impl Normaliser {
+    pub const ALL: [Normaliser; 5] = [
+        Normaliser::Nfkc,
+        Normaliser::CaseFold,
+        Normaliser::Whitespace,
+        Normaliser::Punctuation,
+        Normaliser::StopWords,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normaliser::Nfkc => "nfkc",
+            Normaliser::CaseFold => "case-fold",
+            Normaliser::Whitespace => "whitespace",
+            Normaliser::Punctuation => "punctuation",
+            Normaliser::StopWords => "stop-words",
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Normaliser::Nfkc => text.nfkc().collect(),
+            Normaliser::CaseFold => text.to_lowercase(),
+            Normaliser::Whitespace => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            Normaliser::Punctuation => text
+                .chars()
+                .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+                .collect(),
+            Normaliser::StopWords => text
+                .split_whitespace()
+                .filter(|word| !STOP_WORDS.contains(&word.to_lowercase().as_str()))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+impl fmt::Display for Normaliser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Normaliser {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Normaliser::ALL
+            .into_iter()
+            .find(|n| n.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Normaliser::ALL.iter().map(|n| n.name()).collect();
+                format!(
+                    "unknown normaliser '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+// Run every step of the pipeline over a record
+pub fn normalise(text: &str, steps: &[Normaliser]) -> String {
+    steps
+        .iter()
+        .fold(text.to_string(), |text, step| step.apply(&text))
+}
+
+// A minimal subset of the steps that still makes all originals of a group collide
+pub fn explain_collision(originals: &[&str], steps: &[Normaliser]) -> Vec<Normaliser> {
+    explain_collision_by(originals, steps, normalise)
+}
+
+// Same as explain_collision for records that apply the steps their own way
+pub fn explain_collision_by<F>(
+    originals: &[&str],
+    steps: &[Normaliser],
+    apply: F,
+) -> Vec<Normaliser>
+where
+    F: Fn(&str, &[Normaliser]) -> String,
+{
+    let distinct: HashSet<&str> = originals.iter().copied().collect();
+    let count_forms = |steps: &[Normaliser]| {
+        distinct
+            .iter()
+            .map(|original| apply(original, steps))
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    let target = count_forms(steps);
+
+    let mut needed = steps.to_vec();
+    for step in steps {
+        let without: Vec<Normaliser> = needed.iter().copied().filter(|s| s != step).collect();
+        if count_forms(&without) == target {
+            needed = without;
+        }
+    }
+    needed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalise_pipeline() {
+        let steps = [
+            Normaliser::Nfkc,
+            Normaliser::CaseFold,
+            Normaliser::Punctuation,
+            Normaliser::Whitespace,
+        ];
+        assert_eq!(
+            normalise("  The ﬁsh,   OLD man! ", &steps),
+            "the fish old man"
+        );
+        assert_eq!(
+            normalise("a man can be destroyed", &[Normaliser::StopWords]),
+            "man can destroyed"
+        );
+    }
+
+    #[test]
+    fn test_explain_collision() {
+        let steps = [Normaliser::CaseFold, Normaliser::Whitespace];
+        assert_eq!(
+            explain_collision(&["The Old Man", "the old man "], &steps),
+            vec![Normaliser::CaseFold, Normaliser::Whitespace]
+        );
+
+        let steps = [Normaliser::CaseFold, Normaliser::StopWords];
+        assert_eq!(
+            explain_collision(&["old man", "the old man", "old man"], &steps),
+            vec![Normaliser::StopWords]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Structured results of a duplicate analysis.
+
+A DuplicateReport holds the totals that used to be printed by
+analyze_duplicates together with one DuplicateGroup per repeated hash.
+Groups are ordered by the position where the record was first seen, so the
+same input always produces the same report. Reports can be rendered as the
+original text summary, as JSON or as a CSV audit table.
+
+When fuzzy matching is enabled the report also carries the near-duplicate
+clusters. They appear in the text and JSON output; the CSV table only lists
+the exact groups.
+*/
+
+use crate::fuzzy::NearDuplicateCluster;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+// Records that share the same hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    pub count: usize,
+    pub first_seen: usize,
+    pub positions: Vec<usize>,
+    pub record: String,
+    // Normalisers that made different originals collide, empty for exact copies
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normalisations: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DuplicateReport {
+    // Name of the hash algorithm that produced the digests
+    pub algorithm: String,
+    pub total_records: usize,
+    pub unique_records: usize,
+    // Number of hashes seen more than once
+    pub duplicate_groups: usize,
+    // Number of records that repeat an earlier one
+    pub duplicate_records: usize,
+    pub groups: Vec<DuplicateGroup>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub near_duplicates: Vec<NearDuplicateCluster>,
+}
+
+// One CSV row per duplicate group, positions and normalisations joined with ';'
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    hash: &'a str,
+    count: usize,
+    first_seen: usize,
+    positions: String,
+    record: &'a str,
+    normalisations: String,
+}
+
+impl DuplicateReport {
+    pub fn has_duplicates(&self) -> bool {
+        self.duplicate_records > 0
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> csv::Result<()> {
+        // The header is written even when there are no groups to serialize
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        wtr.write_record([
+            "hash",
+            "count",
+            "first_seen",
+            "positions",
+            "record",
+            "normalisations",
+        ])?;
+        for group in &self.groups {
+            let positions: Vec<String> = group.positions.iter().map(|p| p.to_string()).collect();
+            wtr.serialize(CsvRow {
+                hash: &group.hash,
+                count: group.count,
+                first_seen: group.first_seen,
+                positions: positions.join(";"),
+                record: &group.record,
+                normalisations: group.normalisations.join(";"),
+            })?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for DuplicateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Hash algorithm: {}", self.algorithm)?;
+        writeln!(f, "Total number of records: {}", self.total_records)?;
+        for group in &self.groups {
+            write!(
+                f,
+                "{} - {} times: {}",
+                group.hash, group.count, group.record
+            )?;
+            if group.normalisations.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, " (after {})", group.normalisations.join(", "))?;
+            }
+        }
+        for cluster in &self.near_duplicates {
+            writeln!(
+                f,
+                "Near duplicates (similarity >= {:.2}):",
+                cluster.min_similarity
+            )?;
+            for (position, record) in cluster.positions.iter().zip(&cluster.records) {
+                writeln!(f, "    {}: {}", position, record)?;
+            }
+        }
+        writeln!(f, "Total Unique Records: {}", self.unique_records)?;
+        writeln!(f, "Total Unique Duplicates: {}", self.duplicate_groups)?;
+        write!(f, "Total Combined Duplicates: {}", self.duplicate_records)?;
+        if !self.near_duplicates.is_empty() {
+            write!(
+                f,
+                "\nTotal Near Duplicate Clusters: {}",
+                self.near_duplicates.len()
+            )?;
+        }
+        Ok(())
+    }
+}
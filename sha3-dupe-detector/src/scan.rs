@@ -0,0 +1,380 @@
+/*
+Walks one or more directory trees and groups files with identical contents.
+
+Files are first bucketed by size, so only files that share a size with at
+least one other file are read and hashed (SHA3-256 unless another
+DupeHasher is given), in parallel across all cores. Files sharing a digest
+form a duplicate group; every copy beyond the first is wasted space. Empty
+files and symbolic links are ignored.
+
+With normalisers every non-empty file is read as text (invalid UTF-8
+replaced), run through the pipeline and hashed, whatever its size, since
+normalising can make files of different sizes equal. Files are read whole
+in that case, and the size of a group is that of its first path.
+
+With fuzzy matching every non-empty file is also read as text, normalised
+and summarised by a MinHash signature, and files with similar but not
+identical contents are clustered. Only one file of each duplicate group
+takes part. The clusters are part of the text and JSON output; the CSV
+table only lists the duplicate groups.
+*/
+
+use crate::fuzzy::{cluster_signatures, signature, MinHashConfig};
+use crate::hasher::{DupeHasher, Sha3_256Hasher};
+use crate::normalise::{normalise, Normaliser};
+use crate::MatchOptions;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// A set of files with identical contents
+#[derive(Debug, Serialize)]
+pub struct FileGroup {
+    pub hash: String,
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl FileGroup {
+    // Bytes that would be reclaimed by keeping a single copy
+    pub fn wasted_bytes(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+// Files with similar but not identical contents
+#[derive(Debug, Serialize)]
+pub struct SimilarFiles {
+    pub paths: Vec<PathBuf>,
+    // Lowest estimated similarity of the pairs that formed the cluster
+    pub min_similarity: f64,
+}
+
+// Files with the size of each
+type SizedPaths = Vec<(PathBuf, u64)>;
+
+// Result of scanning a set of paths for duplicate files
+#[derive(Debug, Default)]
+pub struct FileScan {
+    pub algorithm: String,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub groups: Vec<FileGroup>,
+    pub near_duplicates: Vec<SimilarFiles>,
+    pub errors: Vec<String>,
+}
+
+impl FileScan {
+    // Number of files that are copies of another file
+    pub fn duplicate_files(&self) -> usize {
+        self.groups.iter().map(|g| g.paths.len() - 1).sum()
+    }
+
+    pub fn wasted_bytes(&self) -> u64 {
+        self.groups.iter().map(FileGroup::wasted_bytes).sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|g| {
+                json!({
+                    "hash": g.hash,
+                    "size": g.size,
+                    "copies": g.paths.len(),
+                    "wasted_bytes": g.wasted_bytes(),
+                    "paths": g.paths,
+                })
+            })
+            .collect();
+        let mut report = json!({
+            "algorithm": self.algorithm,
+            "total_files": self.total_files,
+            "total_bytes": self.total_bytes,
+            "duplicate_groups": self.groups.len(),
+            "duplicate_files": self.duplicate_files(),
+            "wasted_bytes": self.wasted_bytes(),
+            "groups": groups,
+            "errors": self.errors,
+        });
+        if !self.near_duplicates.is_empty() {
+            report["near_duplicates"] = json!(self.near_duplicates);
+        }
+        serde_json::to_string_pretty(&report)
+    }
+
+    // One CSV row per duplicate file, so the output loads straight into an audit table
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> csv::Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["hash", "size", "copies", "wasted_bytes", "path"])?;
+        for group in &self.groups {
+            for path in &group.paths {
+                wtr.write_record([
+                    group.hash.clone(),
+                    group.size.to_string(),
+                    group.paths.len().to_string(),
+                    group.wasted_bytes().to_string(),
+                    path.display().to_string(),
+                ])?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+// Hash the contents of a file without loading it into memory
+pub fn hash_file<H: DupeHasher>(path: &Path, hasher: &H) -> io::Result<H::Digest> {
+    hasher.digest_reader(File::open(path)?)
+}
+
+// Hash the contents of a file as text after running it through the normalisers
+pub fn hash_file_normalised<H: DupeHasher>(
+    path: &Path,
+    steps: &[Normaliser],
+    hasher: &H,
+) -> io::Result<H::Digest> {
+    Ok(hasher.digest(read_normalised(path, steps)?.as_bytes()))
+}
+
+fn read_normalised(path: &Path, steps: &[Normaliser]) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(normalise(&String::from_utf8_lossy(&bytes), steps))
+}
+
+// Scan files and directories with SHA3-256 and report the groups of duplicate files
+pub fn scan_paths<P: AsRef<Path>>(roots: &[P]) -> FileScan {
+    scan_paths_with(roots, &Sha3_256Hasher)
+}
+
+// Scan files and directories with any hash algorithm
+pub fn scan_paths_with<P: AsRef<Path>, H: DupeHasher>(roots: &[P], hasher: &H) -> FileScan {
+    scan_paths_matching(roots, hasher, &MatchOptions::default())
+}
+
+// Same as scan_paths_with, normalising contents first and clustering similar files
+// when the options ask for it
+pub fn scan_paths_matching<P: AsRef<Path>, H: DupeHasher>(
+    roots: &[P],
+    hasher: &H,
+    options: &MatchOptions,
+) -> FileScan {
+    let mut scan = FileScan {
+        algorithm: hasher.name().to_string(),
+        ..Default::default()
+    };
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+
+    for root in roots {
+        for entry in WalkDir::new(root) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    scan.errors.push(err.to_string());
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            // Overlapping roots must not make a file a duplicate of itself
+            let canonical = entry
+                .path()
+                .canonicalize()
+                .unwrap_or_else(|_| entry.path().to_path_buf());
+            if !seen.insert(canonical) {
+                continue;
+            }
+            let size = match entry.metadata() {
+                Ok(metadata) => metadata.len(),
+                Err(err) => {
+                    scan.errors.push(err.to_string());
+                    continue;
+                }
+            };
+            scan.total_files += 1;
+            scan.total_bytes += size;
+            if size > 0 {
+                by_size.entry(size).or_default().push(entry.into_path());
+            }
+        }
+    }
+
+    let mut files: Vec<PathBuf> = match options.fuzzy {
+        Some(_) => by_size.values().flatten().cloned().collect(),
+        None => Vec::new(),
+    };
+
+    // Only files sharing a size can be duplicates, unless they are normalised;
+    // hash the candidates on all cores
+    let steps = &options.normalise;
+    let candidates: Vec<(u64, PathBuf)> = by_size
+        .into_iter()
+        .filter(|(_, paths)| !steps.is_empty() || paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (size, path)))
+        .collect();
+    let hashed: Vec<_> = candidates
+        .into_par_iter()
+        .map(|(size, path)| {
+            let hash = if steps.is_empty() {
+                hash_file(&path, hasher)
+            } else {
+                hash_file_normalised(&path, steps, hasher)
+            };
+            (size, hash, path)
+        })
+        .collect();
+
+    let mut by_hash: HashMap<(u64, H::Digest), SizedPaths> = HashMap::new();
+    for (size, hash, path) in hashed {
+        // Normalised copies may differ in size, so the size is not part of their key
+        let key_size = if steps.is_empty() { size } else { 0 };
+        match hash {
+            Ok(hash) => by_hash
+                .entry((key_size, hash))
+                .or_default()
+                .push((path, size)),
+            Err(err) => scan.errors.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+    for ((_, hash), mut files) in by_hash {
+        if files.len() > 1 {
+            files.sort();
+            scan.groups.push(FileGroup {
+                hash: hex::encode(hash),
+                size: files[0].1,
+                paths: files.into_iter().map(|(path, _)| path).collect(),
+            });
+        }
+    }
+
+    // Largest savings first, ties broken by path so output is stable
+    scan.groups.sort_by(|a, b| {
+        b.wasted_bytes()
+            .cmp(&a.wasted_bytes())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+
+    if let Some(config) = &options.fuzzy {
+        let copies: HashSet<&PathBuf> = scan.groups.iter().flat_map(|g| &g.paths[1..]).collect();
+        files.retain(|path| !copies.contains(path));
+        files.sort();
+        scan.near_duplicates = similar_files(files, steps, config, &mut scan.errors);
+    }
+    scan.errors.sort();
+
+    scan
+}
+
+// Cluster files by the MinHash signatures of their normalised contents
+fn similar_files(
+    files: Vec<PathBuf>,
+    steps: &[Normaliser],
+    config: &MinHashConfig,
+    errors: &mut Vec<String>,
+) -> Vec<SimilarFiles> {
+    let signed: Vec<(PathBuf, io::Result<Vec<u64>>)> = files
+        .into_par_iter()
+        .map(|path| {
+            let sig = read_normalised(&path, steps).map(|text| signature(&text, config));
+            (path, sig)
+        })
+        .collect();
+
+    let mut paths = Vec::with_capacity(signed.len());
+    let mut signatures = Vec::with_capacity(signed.len());
+    for (path, sig) in signed {
+        match sig {
+            Ok(sig) => {
+                paths.push(path);
+                signatures.push(sig);
+            }
+            Err(err) => errors.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    cluster_signatures(&signatures, config)
+        .into_iter()
+        .map(|(members, min_similarity)| SimilarFiles {
+            paths: members.iter().map(|&i| paths[i].clone()).collect(),
+            min_similarity,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_scan_paths_groups_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(dir.path().join("a.txt"), "the old man").unwrap();
+        fs::write(nested.join("b.txt"), "the old man").unwrap();
+        fs::write(dir.path().join("c.txt"), "the old boy").unwrap();
+        fs::write(dir.path().join("empty.txt"), "").unwrap();
+
+        let scan = scan_paths(&[dir.path(), nested.as_path()]);
+
+        assert_eq!(scan.total_files, 4);
+        assert_eq!(scan.groups.len(), 1);
+        assert_eq!(scan.groups[0].paths.len(), 2);
+        assert_eq!(scan.groups[0].size, 11);
+        assert_eq!(scan.wasted_bytes(), 11);
+        assert_eq!(scan.duplicate_files(), 1);
+    }
+
+    #[test]
+    fn test_scan_paths_normalised() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "The Old Man\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "the old man").unwrap();
+        fs::write(dir.path().join("c.txt"), "the old boy").unwrap();
+
+        let options = MatchOptions {
+            normalise: vec![Normaliser::CaseFold, Normaliser::Whitespace],
+            ..Default::default()
+        };
+        let scan = scan_paths_matching(&[dir.path()], &Sha3_256Hasher, &options);
+
+        assert_eq!(scan.groups.len(), 1);
+        assert_eq!(
+            scan.groups[0].paths,
+            vec![dir.path().join("a.txt"), dir.path().join("b.txt")]
+        );
+        assert_eq!(scan.groups[0].size, 12);
+    }
+
+    #[test]
+    fn test_scan_paths_clusters_similar_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("a.txt"), "man can be destroyed but not defeated").unwrap();
+        fs::write(path("b.txt"), "man can be destroyed but not defeated").unwrap();
+        fs::write(path("c.txt"), "a man can be destroyed but not defeated").unwrap();
+        fs::write(path("d.txt"), "the old man was thin and gaunt").unwrap();
+
+        let options = MatchOptions {
+            fuzzy: Some(MinHashConfig::default()),
+            ..Default::default()
+        };
+        let scan = scan_paths_matching(&[dir.path()], &Sha3_256Hasher, &options);
+
+        assert_eq!(scan.groups.len(), 1);
+        assert_eq!(scan.near_duplicates.len(), 1);
+        assert_eq!(
+            scan.near_duplicates[0].paths,
+            vec![path("a.txt"), path("c.txt")]
+        );
+    }
+}
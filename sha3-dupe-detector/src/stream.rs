@@ -0,0 +1,253 @@
+/*
+Streaming line-level dedup filter.
+
+Lines are read one at a time and only their digest is kept (32 bytes with
+the default SHA3-256, 16 with XXH3), so memory grows with the number of
+distinct lines rather than with the size of the input. Lines are compared
+as raw bytes without the trailing newline, which means files that are not
+valid UTF-8 can be filtered as well.
+
+With a DigestIndex the filter also remembers earlier runs: lines whose digest
+was saved by a previous batch count as duplicates, and the digests of new
+lines are added to the index.
+
+With normalisers each line is decoded as UTF-8 (invalid bytes replaced) and
+run through the pipeline before hashing, while the output keeps the line as
+it was read. An index only makes sense with the same normalisers every run.
+
+With fuzzy matching a line is also a duplicate when its MinHash signature is
+close enough to a line that was already kept. That needs the signature of
+every kept line in memory, and only applies within one run: the index only
+holds exact digests.
+*/
+
+use crate::fuzzy::NearDuplicateIndex;
+use crate::hasher::{DupeHasher, Sha3_256Hasher};
+use crate::index::DigestIndex;
+use crate::normalise::normalise;
+use crate::MatchOptions;
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
+
+// Which occurrences of a line are written to the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamMode {
+    // Only the first occurrence of every line
+    Unique,
+    // Only the occurrences that repeat an earlier line
+    Duplicates,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StreamStats {
+    pub lines_read: usize,
+    pub lines_written: usize,
+    pub unique_lines: usize,
+    // Lines already recorded in the index by an earlier run
+    pub previously_seen: usize,
+    // New lines dropped as near-duplicates of a kept line, not counted as unique
+    pub near_duplicates: usize,
+}
+
+impl StreamStats {
+    pub fn duplicate_lines(&self) -> usize {
+        self.lines_read - self.unique_lines
+    }
+}
+
+// Copy lines from reader to writer, keeping either the first occurrences or the repeats
+pub fn dedup_lines<R: BufRead, W: Write>(
+    reader: R,
+    writer: W,
+    mode: StreamMode,
+) -> io::Result<StreamStats> {
+    dedup_lines_with(reader, writer, mode, &Sha3_256Hasher)
+}
+
+// Same as dedup_lines with any hash algorithm
+pub fn dedup_lines_with<R: BufRead, W: Write, H: DupeHasher>(
+    reader: R,
+    writer: W,
+    mode: StreamMode,
+    hasher: &H,
+) -> io::Result<StreamStats> {
+    let mut index = DigestIndex::new(hasher);
+    dedup_lines_indexed(reader, writer, mode, hasher, &mut index)
+}
+
+// Dedup against the digests of earlier runs as well, adding the new ones to the index
+pub fn dedup_lines_indexed<R: BufRead, W: Write, H: DupeHasher>(
+    reader: R,
+    writer: W,
+    mode: StreamMode,
+    hasher: &H,
+    index: &mut DigestIndex<H::Digest>,
+) -> io::Result<StreamStats> {
+    dedup_lines_matching(
+        reader,
+        writer,
+        mode,
+        hasher,
+        index,
+        &MatchOptions::default(),
+    )
+}
+
+// Same as dedup_lines_indexed, normalising lines first and dropping near-duplicates
+// when the options ask for it
+pub fn dedup_lines_matching<R: BufRead, W: Write, H: DupeHasher>(
+    mut reader: R,
+    mut writer: W,
+    mode: StreamMode,
+    hasher: &H,
+    index: &mut DigestIndex<H::Digest>,
+    options: &MatchOptions,
+) -> io::Result<StreamStats> {
+    let mut near = options.fuzzy.map(NearDuplicateIndex::new);
+    let mut stats = StreamStats::default();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        stats.lines_read += 1;
+
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        let normalised = if options.normalise.is_empty() {
+            Cow::Borrowed(content)
+        } else {
+            let text = normalise(&String::from_utf8_lossy(content), &options.normalise);
+            Cow::Owned(text.into_bytes())
+        };
+        let digest = hasher.digest(&normalised);
+        if index.seen_before(&digest) {
+            stats.previously_seen += 1;
+        }
+        let mut first = index.insert(digest);
+        if let (true, Some(near)) = (first, near.as_mut()) {
+            if !near.insert(&String::from_utf8_lossy(&normalised)) {
+                first = false;
+                stats.near_duplicates += 1;
+            }
+        }
+        if first {
+            stats.unique_lines += 1;
+        }
+
+        if first == (mode == StreamMode::Unique) {
+            writer.write_all(&line)?;
+            stats.lines_written += 1;
+        }
+    }
+    writer.flush()?;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Normaliser;
+
+    #[test]
+    fn test_dedup_lines_modes() {
+        let input = "a\nb\na\nc\nb\na";
+
+        let mut unique = Vec::new();
+        let stats = dedup_lines(input.as_bytes(), &mut unique, StreamMode::Unique).unwrap();
+        assert_eq!(String::from_utf8(unique).unwrap(), "a\nb\nc\n");
+        assert_eq!(stats.lines_read, 6);
+        assert_eq!(stats.unique_lines, 3);
+        assert_eq!(stats.duplicate_lines(), 3);
+
+        let mut dupes = Vec::new();
+        dedup_lines(input.as_bytes(), &mut dupes, StreamMode::Duplicates).unwrap();
+        assert_eq!(String::from_utf8(dupes).unwrap(), "a\nb\na");
+    }
+
+    #[test]
+    fn test_dedup_lines_against_earlier_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.idx");
+        let hasher = Sha3_256Hasher;
+
+        let mut index = DigestIndex::open(&path, &hasher).unwrap();
+        dedup_lines_indexed(
+            "a\nb\n".as_bytes(),
+            io::sink(),
+            StreamMode::Unique,
+            &hasher,
+            &mut index,
+        )
+        .unwrap();
+        index.save(&path).unwrap();
+
+        let mut index = DigestIndex::open(&path, &hasher).unwrap();
+        let mut out = Vec::new();
+        let stats = dedup_lines_indexed(
+            "b\nc\nc\n".as_bytes(),
+            &mut out,
+            StreamMode::Unique,
+            &hasher,
+            &mut index,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "c\n");
+        assert_eq!(stats.previously_seen, 1);
+        assert_eq!(stats.unique_lines, 1);
+    }
+
+    #[test]
+    fn test_dedup_lines_normalised() {
+        let options = MatchOptions {
+            normalise: vec![Normaliser::CaseFold, Normaliser::Whitespace],
+            ..Default::default()
+        };
+        let hasher = Sha3_256Hasher;
+        let mut out = Vec::new();
+        let stats = dedup_lines_matching(
+            "The Old Man\nthe  old man \nthe old boy\n".as_bytes(),
+            &mut out,
+            StreamMode::Unique,
+            &hasher,
+            &mut DigestIndex::new(&hasher),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "The Old Man\nthe old boy\n"
+        );
+        assert_eq!(stats.duplicate_lines(), 1);
+    }
+
+    #[test]
+    fn test_dedup_lines_fuzzy() {
+        let input = "man can be destroyed but not defeated\n\
+            a man can be destroyed but not defeated\n\
+            the old man was thin and gaunt\n";
+        let options = MatchOptions {
+            fuzzy: Some(Default::default()),
+            ..Default::default()
+        };
+        let hasher = Sha3_256Hasher;
+        let mut out = Vec::new();
+        let stats = dedup_lines_matching(
+            input.as_bytes(),
+            &mut out,
+            StreamMode::Unique,
+            &hasher,
+            &mut DigestIndex::new(&hasher),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "man can be destroyed but not defeated\nthe old man was thin and gaunt\n"
+        );
+        assert_eq!(stats.near_duplicates, 1);
+        assert_eq!(stats.duplicate_lines(), 1);
+    }
+}